@@ -0,0 +1,73 @@
+//! Benchmarks over a generated 256x256 map: `cargo bench`.
+//!
+//! The game is a binary, so its sources are built into the benchmark
+//! directly.
+
+#![allow(dead_code)]
+
+extern crate nalgebra as na;
+extern crate nalgebra_glm as glm;
+
+#[path = "../src/rendering/mod.rs"]
+mod rendering;
+#[path = "../src/game/mod.rs"]
+mod game;
+#[path = "../src/uglythings.rs"]
+mod uglythings;
+
+use criterion::{ criterion_group, criterion_main, BenchmarkId, Criterion };
+use game::{
+    pathfinding::{ find_path, FlowField },
+    textures::TextureRegistry
+};
+use rendering::level_mesh::{ build_door_geometry, build_level_geometry };
+use uglythings::{ generated_map, per_cell_draw_calls };
+
+/// Side of the generated map, in cells.
+const MAP_SIZE: usize = 256;
+
+fn registry() -> TextureRegistry {
+    TextureRegistry {
+        textures: vec!["wall".to_string(), "floor".to_string(), "ceiling".to_string()],
+        floor: 1,
+        ceiling: 2,
+        door: 0,
+        impact: None,
+        sheets: Vec::new(),
+        view_model: None
+    }
+}
+
+/// Times baking the map, and names the draw calls the baked meshes take
+/// against the ones the per-cell renderer took.
+fn level_geometry(c: &mut Criterion) {
+    let world = generated_map(MAP_SIZE);
+    let registry = registry();
+    let batched = build_level_geometry(&world, &registry).draw_calls()
+        + build_door_geometry(&world, &registry).draw_calls();
+    let draw_calls = format!("{} draw calls vs {} per cell", batched, per_cell_draw_calls(&world));
+    let mut group = c.benchmark_group("256x256");
+
+    group.bench_function(BenchmarkId::new("build_level_geometry", &draw_calls), |b| {
+        b.iter(|| build_level_geometry(&world, &registry))
+    });
+    group.bench_function("build_door_geometry", |b| {
+        b.iter(|| build_door_geometry(&world, &registry))
+    });
+    group.finish();
+}
+
+fn pathfinding(c: &mut Criterion) {
+    let floor = generated_map(MAP_SIZE);
+    let far_corner = (MAP_SIZE as i32 - 2, MAP_SIZE as i32 - 2);
+
+    c.bench_function("find_path across 256x256", |b| {
+        b.iter(|| find_path(&floor, (1, 1), far_corner, Some(5.0)))
+    });
+    c.bench_function("FlowField::toward over 256x256", |b| {
+        b.iter(|| FlowField::toward(&floor, far_corner, Some(5.0)))
+    });
+}
+
+criterion_group!(benches, level_geometry, pathfinding);
+criterion_main!(benches);
@@ -12,11 +12,14 @@ uniform vec2 u_resolution;
 
 in vec4 a_position;
 in vec2 a_tex_coord;
+in float a_layer;
 
 smooth out vec2 tex_coord;
+flat out float layer;
 
 void main()
 {
     gl_Position = u_projection_matrix * u_model_matrix * a_position;
     tex_coord = a_tex_coord;
+    layer = a_layer;
 }
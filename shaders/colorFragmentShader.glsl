@@ -0,0 +1,14 @@
+///totw fragment_shader
+#version 140
+
+#ifdef GL_ES
+precision mediump float;
+#endif
+
+uniform vec2 u_resolution;
+// Every fragment gets this color.
+uniform vec4 u_color;
+
+void main() {
+    gl_FragColor = u_color;
+}
@@ -7,9 +7,11 @@ precision mediump float;
 
 uniform vec2 u_resolution;
 uniform sampler2D u_texture;
+// Glyphs are white, tinted by this color.
+uniform vec4 u_color;
 
 smooth in vec2 tex_coord;
 
 void main() {
-    gl_FragColor = texture(u_texture, tex_coord);
+    gl_FragColor = texture(u_texture, tex_coord) * u_color;
 }
@@ -0,0 +1,23 @@
+///totw fragment_shader
+#version 140
+
+#ifdef GL_ES
+precision mediump float;
+#endif
+
+uniform vec2 u_resolution;
+uniform sampler2DArray u_texture0;
+
+smooth in vec2 tex_coord;
+flat in float layer;
+
+void main() {
+    vec4 color = texture(u_texture0, vec3(tex_coord, layer));
+
+    // Sprites leave out their background.
+    if (color.a < 0.5) {
+        discard;
+    }
+
+    gl_FragColor = color;
+}
@@ -0,0 +1,465 @@
+//! Turns the state of the input devices into `InputState`s.
+//!
+//! Bindings come from `controls.ron`, which maps each action to SDL key
+//! names (as in `Keycode::from_name`), mouse buttons (`Left`, `Middle`,
+//! `Right`, `X1`, `X2`) and game controller buttons (as in
+//! `Button::from_string`). Moving the mouse sideways turns the player, by
+//! `mouse_sensitivity` radians per pixel. On game controllers the left stick
+//! walks and strafes and the right stick turns, ignoring tilts smaller than
+//! `stick_dead_zone`.
+
+use std::collections::{ BTreeMap, HashMap, HashSet };
+use std::hash::Hash;
+use std::path::Path;
+use serde::{ Serialize, Deserialize };
+use sdl2::{
+    EventPump, GameControllerSubsystem,
+    controller::{ Axis, Button, GameController },
+    event::Event,
+    keyboard::{ Keycode, Scancode },
+    mouse::MouseButton
+};
+use crate::game::input::{ Action, InputState };
+
+/// A single input bound to an action, named as in the config file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum BindingRon {
+    Key(String),
+    Mouse(String),
+    Button(String)
+}
+
+/// On-disk layout of `controls.ron`.
+#[derive(Serialize, Deserialize)]
+struct ControlsRon {
+    bindings: BTreeMap<Action, Vec<BindingRon>>,
+    #[serde(default = "default_mouse_sensitivity")]
+    mouse_sensitivity: f32,
+    /// Turn right when the mouse moves left.
+    #[serde(default)]
+    invert_mouse: bool,
+    /// Fraction of the stick travel, from 0 to 1, that counts as centered.
+    #[serde(default = "default_stick_dead_zone")]
+    stick_dead_zone: f32
+}
+
+impl Default for ControlsRon {
+    fn default() -> Self {
+        use BindingRon::*;
+
+        let key = |name: &str| Key(name.to_string());
+        let button = |name: &str| Button(name.to_string());
+        let bindings = vec![
+            (Action::Forward, vec![key("Up"), key("W"), button("dpup")]),
+            (Action::Back, vec![key("Down"), key("S"), button("dpdown")]),
+            (Action::StrafeLeft, vec![key("A")]),
+            (Action::StrafeRight, vec![key("D")]),
+            (Action::TurnLeft, vec![key("Left"), button("dpleft")]),
+            (Action::TurnRight, vec![key("Right"), button("dpright")]),
+            (Action::Use, vec![key("Space"), key("E"), button("a")]),
+            (Action::Fire, vec![
+                key("Left Ctrl"), Mouse("Left".to_string()), button("rightshoulder")
+            ]),
+            (Action::Map, vec![key("Tab"), button("back")]),
+            (Action::MapFullScreen, vec![key("M")]),
+            (Action::MapZoomIn, vec![key("="), key("Keypad +")]),
+            (Action::MapZoomOut, vec![key("-"), key("Keypad -")]),
+            (Action::MapPanUp, vec![key("I")]),
+            (Action::MapPanDown, vec![key("K")]),
+            (Action::MapPanLeft, vec![key("J")]),
+            (Action::MapPanRight, vec![key("L")]),
+            (Action::MapRecenter, vec![key("Home")])
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+            mouse_sensitivity: default_mouse_sensitivity(),
+            invert_mouse: false,
+            stick_dead_zone: default_stick_dead_zone()
+        }
+    }
+}
+
+/// Inputs bound to actions, each input to a single action.
+#[derive(Debug)]
+struct Bindings {
+    keys: HashMap<Scancode, Action>,
+    mouse_buttons: HashMap<MouseButton, Action>,
+    buttons: HashMap<Button, Action>
+}
+
+impl Bindings {
+    /// Looks up the inputs named in the config file.
+    /// Returns: an error naming the input that is unknown, or bound to two
+    /// actions.
+    fn from_ron(bindings: &BTreeMap<Action, Vec<BindingRon>>) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        let mut mouse_buttons = HashMap::new();
+        let mut buttons = HashMap::new();
+
+        for (action, inputs) in bindings.iter() {
+            for input in inputs.iter() {
+                match input {
+                    BindingRon::Key(name) => {
+                        let scancode = Keycode::from_name(name)
+                            .and_then(Scancode::from_keycode)
+                            .ok_or_else(|| format!("{:?}: unknown key \"{}\"", action, name))?;
+                        bind(&mut keys, scancode, *action, "key", name)?;
+                    }
+                    BindingRon::Mouse(name) => {
+                        let button = mouse_button_from_name(name)
+                            .ok_or_else(|| format!("{:?}: unknown mouse button \"{}\"", action, name))?;
+                        bind(&mut mouse_buttons, button, *action, "mouse button", name)?;
+                    }
+                    BindingRon::Button(name) => {
+                        let button = Button::from_string(name)
+                            .ok_or_else(|| format!("{:?}: unknown controller button \"{}\"", action, name))?;
+                        bind(&mut buttons, button, *action, "controller button", name)?;
+                    }
+                }
+            }
+        }
+
+        Ok(Self { keys, mouse_buttons, buttons })
+    }
+}
+
+/// Binds an input to an action, unless another action has it already.
+/// * `kind`, `name`: describe the input in the error.
+fn bind<T: Eq + Hash>(
+    bound: &mut HashMap<T, Action>, input: T, action: Action, kind: &str, name: &str
+) -> Result<(), String> {
+    match bound.insert(input, action) {
+        Some(other) if other != action => Err(format!(
+            "{} \"{}\" bound to both {:?} and {:?}", kind, name, other, action
+        )),
+        _ => Ok(())
+    }
+}
+
+fn default_mouse_sensitivity() -> f32 {
+    0.003
+}
+
+fn default_stick_dead_zone() -> f32 {
+    0.2
+}
+
+/// Analog axes read from the controller sticks.
+#[derive(Copy, Clone, Default)]
+struct Sticks {
+    forward: f32,
+    strafe: f32,
+    turn: f32
+}
+
+/// Tracks which actions are held, from the device state rather than from
+/// events, so that key repeat does not matter and any number of keys can be
+/// held together.
+pub struct Controls {
+    controller_subsystem: GameControllerSubsystem,
+    /// Plugged in controllers, by joystick instance id.
+    controllers: HashMap<u32, GameController>,
+    keys: HashMap<Scancode, Action>,
+    mouse_buttons: HashMap<MouseButton, Action>,
+    buttons: HashMap<Button, Action>,
+    held: HashSet<Action>,
+    /// Actions that went down since the last `consume`.
+    pressed: HashSet<Action>,
+    /// Radians per pixel of mouse motion, negative when inverted.
+    mouse_yaw_scale: f32,
+    /// Radians the mouse turned since the last `consume`, left positive.
+    yaw: f32,
+    stick_dead_zone: f32,
+    sticks: Sticks
+}
+
+impl Controls {
+    /// Loads the bindings from a RON file, writing the default bindings
+    /// there first if the file does not exist.
+    /// * `path`: usually `controls.ron`.
+    /// * `controller_subsystem`: opens the controllers as they get plugged in.
+    /// Returns: `Result<Controls, String>`.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P, controller_subsystem: GameControllerSubsystem
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            let defaults = ron::ser::to_string_pretty(
+                &ControlsRon::default(), ron::ser::PrettyConfig::default()
+            ).map_err(|e| format!("{}", e))?;
+
+            std::fs::write(path, defaults)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        let source = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.display(), error));
+            }
+        };
+        let controls: ControlsRon = ron::de::from_str(&source)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        Self::from_ron(&controls, controller_subsystem)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn from_ron(
+        controls: &ControlsRon, controller_subsystem: GameControllerSubsystem
+    ) -> Result<Self, String> {
+        let Bindings { keys, mouse_buttons, buttons } = Bindings::from_ron(&controls.bindings)?;
+        let stick_dead_zone = validate_dead_zone(controls.stick_dead_zone)?;
+
+        Ok(Self {
+            controller_subsystem,
+            controllers: HashMap::new(),
+            keys, mouse_buttons, buttons,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            mouse_yaw_scale: if controls.invert_mouse {
+                -controls.mouse_sensitivity
+            } else {
+                controls.mouse_sensitivity
+            },
+            yaw: 0.0,
+            stick_dead_zone,
+            sticks: Sticks::default()
+        })
+    }
+
+    /// Opens and closes controllers as they get plugged in and out. SDL also
+    /// reports the controllers already plugged in at startup this way.
+    /// Returns: an error naming the controller that could not be opened.
+    pub fn handle_event(&mut self, event: &Event) -> Result<(), String> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                let controller = self.controller_subsystem.open(which)
+                    .map_err(|e| format!("cannot open controller {}: {}", which, e))?;
+
+                self.controllers.insert(controller.instance_id(), controller);
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.remove(&which);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Samples the devices. Call after the events have been polled.
+    pub fn update(&mut self, event_pump: &EventPump) {
+        let keyboard = event_pump.keyboard_state();
+        let mouse = event_pump.mouse_state();
+        // Moving right turns right, which is a negative angle.
+        self.yaw -= event_pump.relative_mouse_state().x() as f32 * self.mouse_yaw_scale;
+        let mut held: HashSet<Action> = keyboard.pressed_scancodes()
+            .filter_map(|scancode| self.keys.get(&scancode).cloned())
+            .chain(mouse.pressed_mouse_buttons()
+                .filter_map(|button| self.mouse_buttons.get(&button).cloned()))
+            .collect();
+        let mut sticks = Sticks::default();
+
+        for controller in self.controllers.values() {
+            held.extend(self.buttons.iter()
+                .filter(|(button, _)| controller.button(**button))
+                .map(|(_, action)| *action));
+
+            let (x, y) = dead_zone(
+                stick_value(controller, Axis::LeftX),
+                stick_value(controller, Axis::LeftY),
+                self.stick_dead_zone
+            );
+            let (turn, _) = dead_zone(
+                stick_value(controller, Axis::RightX),
+                stick_value(controller, Axis::RightY),
+                self.stick_dead_zone
+            );
+
+            // Stick y grows downwards, and pushing right turns right.
+            sticks.forward -= y;
+            sticks.strafe += x;
+            sticks.turn -= turn;
+        }
+
+        self.sticks = sticks;
+
+        for action in held.difference(&self.held) {
+            self.pressed.insert(*action);
+        }
+
+        self.held = held;
+    }
+
+    pub fn input_state(&self) -> InputState {
+        InputState {
+            forward: clamp_axis(
+                self.axis(Action::Forward, Action::Back) + self.sticks.forward
+            ),
+            strafe: clamp_axis(
+                self.axis(Action::StrafeRight, Action::StrafeLeft) + self.sticks.strafe
+            ),
+            turn: clamp_axis(
+                self.axis(Action::TurnLeft, Action::TurnRight) + self.sticks.turn
+            ),
+            yaw: self.yaw,
+            use_pressed: self.pressed.contains(&Action::Use),
+            fire: self.held.contains(&Action::Fire)
+        }
+    }
+
+    /// Tells whether an action went down since the last `consume`, and
+    /// forgets the press so that it is seen once, for actions handled once
+    /// per frame rather than per tick.
+    pub fn take_press(&mut self, action: Action) -> bool {
+        self.pressed.remove(&action)
+    }
+
+    /// Forgets the presses and the mouse motion, once a tick has seen them.
+    pub fn consume(&mut self) {
+        self.pressed.clear();
+        self.yaw = 0.0;
+    }
+
+    /// Turns a pair of opposite actions into an axis value.
+    fn axis(&self, positive: Action, negative: Action) -> f32 {
+        (self.held.contains(&positive) as i32 - self.held.contains(&negative) as i32) as f32
+    }
+}
+
+/// Reads a stick axis, from -1 to 1.
+fn stick_value(controller: &GameController, axis: Axis) -> f32 {
+    (controller.axis(axis) as f32 / i16::MAX as f32).max(-1.0)
+}
+
+/// Makes sure a dead zone leaves some of the stick travel.
+/// Returns: the dead zone.
+fn validate_dead_zone(size: f32) -> Result<f32, String> {
+    if !(0.0..1.0).contains(&size) {
+        return Err(format!("stick dead zone {} out of range", size));
+    }
+
+    Ok(size)
+}
+
+/// Zeroes the tilts of a stick that fall inside the dead zone, and rescales
+/// the others so that the stick still goes smoothly from 0 to 1.
+/// * `x`, `y`: stick position, from -1 to 1 each.
+/// * `size`: radius of the dead zone, from 0 to 1.
+/// Returns: the new stick position.
+fn dead_zone(x: f32, y: f32, size: f32) -> (f32, f32) {
+    let length = (x * x + y * y).sqrt();
+
+    if length <= size {
+        return (0.0, 0.0);
+    }
+
+    let scale = ((length - size) / (1.0 - size)).min(1.0) / length;
+
+    (x * scale, y * scale)
+}
+
+fn clamp_axis(value: f32) -> f32 {
+    value.clamp(-1.0, 1.0)
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    match name {
+        "Left" => Some(MouseButton::Left),
+        "Middle" => Some(MouseButton::Middle),
+        "Right" => Some(MouseButton::Right),
+        "X1" => Some(MouseButton::X1),
+        "X2" => Some(MouseButton::X2),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Bindings, String> {
+        let controls: ControlsRon = ron::de::from_str(source).map_err(|e| format!("{}", e))?;
+
+        Bindings::from_ron(&controls.bindings)
+    }
+
+    fn length((x, y): (f32, f32)) -> f32 {
+        (x * x + y * y).sqrt()
+    }
+
+    #[test]
+    fn ignores_small_tilts() {
+        assert_eq!(dead_zone(0.0, 0.0, 0.2), (0.0, 0.0));
+        assert_eq!(dead_zone(0.1, -0.1, 0.2), (0.0, 0.0));
+        assert_eq!(dead_zone(0.0, 0.2, 0.2), (0.0, 0.0));
+    }
+
+    #[test]
+    fn rescales_the_rest_of_the_travel() {
+        // Leaving the dead zone starts from 0, not from its size.
+        assert!(length(dead_zone(0.201, 0.0, 0.2)) < 0.01);
+        assert!((length(dead_zone(0.6, 0.0, 0.2)) - 0.5).abs() < 1e-6);
+        assert!((length(dead_zone(1.0, 0.0, 0.2)) - 1.0).abs() < 1e-6);
+        // Corners of the stick travel, further than 1, clamp to 1.
+        assert!((length(dead_zone(1.0, -1.0, 0.2)) - 1.0).abs() < 1e-6);
+
+        let (x, y) = dead_zone(0.3, 0.4, 0.2);
+        assert!((x / y - 0.75).abs() < 1e-6);
+        assert!(x > 0.0 && y > 0.0);
+    }
+
+    #[test]
+    fn rejects_dead_zones_without_travel() {
+        assert_eq!(validate_dead_zone(0.0), Ok(0.0));
+        assert_eq!(validate_dead_zone(0.2), Ok(0.2));
+        assert_eq!(validate_dead_zone(1.0), Err("stick dead zone 1 out of range".to_string()));
+        assert!(validate_dead_zone(-0.1).is_err());
+        assert!(validate_dead_zone(f32::NAN).is_err());
+    }
+
+    #[test]
+    fn reads_the_bindings() {
+        let bindings = parse(
+            r#"(bindings: { Forward: [Key("W"), Button("dpup")], Fire: [Mouse("Left")] })"#
+        ).unwrap();
+
+        assert_eq!(bindings.keys[&Scancode::W], Action::Forward);
+        assert_eq!(bindings.buttons[&Button::DPadUp], Action::Forward);
+        assert_eq!(bindings.mouse_buttons[&MouseButton::Left], Action::Fire);
+        assert_eq!(bindings.keys.len(), 1);
+    }
+
+    #[test]
+    fn reads_the_default_bindings() {
+        let defaults = ron::ser::to_string(&ControlsRon::default()).unwrap();
+
+        assert!(parse(&defaults).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_inputs() {
+        assert_eq!(
+            parse(r#"(bindings: { Use: [Key("Nope")] })"#).unwrap_err(),
+            "Use: unknown key \"Nope\""
+        );
+        assert!(parse(r#"(bindings: { Fire: [Mouse("X3")] })"#).is_err());
+        assert!(parse(r#"(bindings: { Fire: [Button("turbo")] })"#).is_err());
+    }
+
+    #[test]
+    fn rejects_inputs_bound_twice() {
+        assert_eq!(
+            parse(r#"(bindings: { Forward: [Key("W")], Use: [Key("W")] })"#).unwrap_err(),
+            "key \"W\" bound to both Forward and Use"
+        );
+        assert!(parse(r#"(bindings: { Use: [Mouse("Right")], Fire: [Mouse("Right")] })"#)
+            .unwrap_err().contains("Use and Fire"));
+        assert!(parse(r#"(bindings: { Use: [Button("a")], Map: [Button("a")] })"#).is_err());
+        // Listing an input twice for the same action is harmless.
+        assert!(parse(r#"(bindings: { Use: [Key("E"), Key("E")] })"#).is_ok());
+    }
+}
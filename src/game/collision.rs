@@ -0,0 +1,186 @@
+//! Collision of moving circles against the dungeon grid.
+//!
+//! Cells are unit squares centered on integer coordinates, so the cell at
+//! column `x` and row `y` spans `[x - 0.5, x + 0.5]` on both axes.
+
+use glm::Vec3;
+use crate::game::model::DungeonFloor;
+
+/// Radius of the player's collision circle, in cells.
+pub const PLAYER_RADIUS: f32 = 0.3;
+
+/// Keeps resolved circles a hair away from the walls they touch, so that
+/// sliding along a wall does not register as a hit on the next axis.
+const SKIN: f32 = 1e-4;
+
+/// Moves a circle by `delta`, one axis at a time, and stops it against
+/// solid cells. Blocking one axis leaves the other free, which makes the
+/// circle slide along walls.
+/// * `pos`: center of the circle; `z` is left untouched.
+/// * `delta`: requested displacement.
+/// * `radius`: radius of the circle, must be below half a cell.
+/// Returns: the resolved position.
+pub fn slide(floor: &DungeonFloor, pos: Vec3, delta: Vec3, radius: f32) -> Vec3 {
+    let mut result = pos;
+
+    resolve_axis(floor, &mut result, 0, delta.x, radius);
+    resolve_axis(floor, &mut result, 1, delta.y, radius);
+    result.z += delta.z;
+
+    result
+}
+
+/// Tells whether a circle overlaps any solid cell.
+pub fn overlaps_solid(floor: &DungeonFloor, pos: Vec3, radius: f32) -> bool {
+    let (x0, x1) = cell_range(pos.x - radius, pos.x + radius);
+    let (y0, y1) = cell_range(pos.y - radius, pos.y + radius);
+
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            if is_solid(floor, x, y) && overlaps_cell(pos, radius, x, y) {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+/// Tells whether a circle overlaps the cell at column `x` and row `y`.
+pub fn overlaps_cell(pos: Vec3, radius: f32, x: i32, y: i32) -> bool {
+    let dx = distance_outside(pos.x, x as f32);
+    let dy = distance_outside(pos.y, y as f32);
+
+    dx * dx + dy * dy < radius * radius
+}
+
+fn resolve_axis(floor: &DungeonFloor, pos: &mut Vec3, axis: usize, delta: f32, radius: f32) {
+    if delta == 0.0 {
+        return;
+    }
+
+    let other = 1 - axis;
+    let start = pos[axis];
+    let end = start + delta;
+    // Scan the whole swept area, so that fast movers cannot tunnel.
+    let (a0, a1) = cell_range(start.min(end) - radius, start.max(end) + radius);
+    let (o0, o1) = cell_range(pos[other] - radius, pos[other] + radius);
+    let mut resolved = end;
+
+    for a in a0..=a1 {
+        for o in o0..=o1 {
+            let (x, y) = if axis == 0 { (a, o) } else { (o, a) };
+
+            if !is_solid(floor, x, y) {
+                continue;
+            }
+
+            let d_other = distance_outside(pos[other], o as f32);
+
+            if d_other >= radius {
+                continue;
+            }
+
+            // How far the circle reaches along the axis at this offset.
+            let reach = (radius * radius - d_other * d_other).sqrt();
+            let low = a as f32 - 0.5;
+            let high = a as f32 + 0.5;
+
+            if delta > 0.0 && low >= start {
+                resolved = resolved.min(low - reach - SKIN);
+            } else if delta < 0.0 && high <= start {
+                resolved = resolved.max(high + reach + SKIN);
+            }
+        }
+    }
+
+    // Never push the circle backwards if it already started in contact.
+    pos[axis] = if delta > 0.0 { resolved.max(start) } else { resolved.min(start) };
+}
+
+fn is_solid(floor: &DungeonFloor, x: i32, y: i32) -> bool {
+    match floor.cell(x, y) {
+        Some(cell) => cell.is_solid(),
+        None => true
+    }
+}
+
+/// Range of cell indices covered by the interval `[from, to]`.
+fn cell_range(from: f32, to: f32) -> (i32, i32) {
+    ((from + 0.5).floor() as i32, (to + 0.5).floor() as i32)
+}
+
+/// Distance of `value` from the span of the cell centered on `center`, zero
+/// when inside.
+fn distance_outside(value: f32, center: f32) -> f32 {
+    ((value - center).abs() - 0.5).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uglythings::build_experimental_game;
+
+    fn drive(pos: &mut Vec3, floor: &DungeonFloor, delta: Vec3, steps: usize) {
+        for _ in 0..steps {
+            *pos = slide(floor, *pos, delta, PLAYER_RADIUS);
+            assert!(
+                !overlaps_solid(floor, *pos, PLAYER_RADIUS),
+                "player inside a solid cell at {:?}", pos
+            );
+        }
+    }
+
+    #[test]
+    fn stops_in_room_corners() {
+        let game = build_experimental_game();
+        let corners = [
+            (Vec3::new(4.0, 6.5, 0.0), Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, 8.0, 0.0)),
+            (Vec3::new(2.0, 7.0, 0.0), Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 3.0, 0.0)),
+            (Vec3::new(4.0, 6.5, 0.0), Vec3::new(1.0, 1.0, 0.0), Vec3::new(5.0, 8.0, 0.0)),
+            (Vec3::new(4.5, 3.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::new(5.0, 1.0, 0.0)),
+        ];
+
+        for (start, direction, corner) in corners.iter() {
+            let mut pos = *start;
+            drive(&mut pos, &game.current_map, direction * 0.2, 100);
+
+            let distance = glm::distance(&pos, corner);
+            assert!(distance < 0.5, "expected to rest near {:?}, got {:?}",
+                corner, pos);
+        }
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        let game = build_experimental_game();
+        let mut pos = game.player_position();
+
+        // Push diagonally into the south wall: x keeps moving.
+        drive(&mut pos, &game.current_map, Vec3::new(0.1, 0.1, 0.0), 20);
+
+        assert!(pos.x > 2.5);
+        assert!((pos.y - (8.5 - PLAYER_RADIUS)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fast_movers_do_not_tunnel() {
+        let game = build_experimental_game();
+        let mut pos = game.player_position();
+
+        drive(&mut pos, &game.current_map, Vec3::new(-3.0, 0.0, 0.0), 1);
+
+        assert!((pos.x - (0.5 + PLAYER_RADIUS)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rounds_pillar_corners() {
+        let game = build_experimental_game();
+
+        // Graze the corner of the lone pillar at (3, 5) from below-left.
+        let mut pos = Vec3::new(2.0, 6.2, 0.0);
+        drive(&mut pos, &game.current_map, Vec3::new(0.05, -0.05, 0.0), 60);
+        drive(&mut pos, &game.current_map, Vec3::new(0.1, 0.0, 0.0), 30);
+        drive(&mut pos, &game.current_map, Vec3::new(0.0, -0.1, 0.0), 30);
+    }
+}
@@ -0,0 +1,216 @@
+//! Components of the entities that live in the dungeon.
+
+use std::collections::BTreeSet;
+use serde::Deserialize;
+use specs::{ Component, VecStorage, HashMapStorage, NullStorage };
+use glm::Vec3;
+use crate::game::{ model::TextureIndex, pathfinding::Route };
+
+/// Where an entity stands, in cells.
+#[derive(Copy, Clone, Debug)]
+pub struct Position(pub Vec3);
+
+impl Component for Position {
+    type Storage = VecStorage<Self>;
+}
+
+/// How fast an entity moves, in cells per second.
+#[derive(Copy, Clone, Debug)]
+pub struct Velocity(pub Vec3);
+
+impl Component for Velocity {
+    type Storage = VecStorage<Self>;
+}
+
+/// Where an entity looks. Angles grow counterclockwise from the x axis.
+#[derive(Copy, Clone, Debug)]
+pub struct Facing {
+    pub angle: f32,
+    /// Unit vector along `angle`, kept in sync by `turn`.
+    pub direction: Vec3
+}
+
+impl Facing {
+    pub fn new(angle: f32) -> Self {
+        Self {
+            angle,
+            direction: glm::rotate_z_vec3(&Vec3::new(1.0, 0.0, 0.0), angle)
+        }
+    }
+
+    /// Turns by `delta` radians, counterclockwise.
+    pub fn turn(&mut self, delta: f32) {
+        *self = Self::new(self.angle + delta);
+    }
+}
+
+impl Component for Facing {
+    type Storage = VecStorage<Self>;
+}
+
+/// Circle that stops against solid cells.
+#[derive(Copy, Clone, Debug)]
+pub struct Collider {
+    /// In cells, must be below half a cell.
+    pub radius: f32
+}
+
+impl Component for Collider {
+    type Storage = VecStorage<Self>;
+}
+
+/// Tags the entity driven by the player input.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct PlayerControlled;
+
+impl Component for PlayerControlled {
+    type Storage = NullStorage<Self>;
+}
+
+/// Opens the locked doors of its color.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Deserialize)]
+pub enum Key {
+    Gold,
+    Silver
+}
+
+/// Share of the damage armor takes in place of health, while it lasts.
+pub const ARMOR_SHARE: f32 = 1.0 / 3.0;
+
+/// What the player carries, beside health and weapons.
+#[derive(Clone, Default, Debug)]
+pub struct Player {
+    /// Points of damage left for armor to soak up.
+    pub armor: i32,
+    pub keys: BTreeSet<Key>,
+    /// Cells the player has seen, by cell index, see `game::exploration`.
+    pub explored: Vec<bool>
+}
+
+impl Player {
+    /// Lets armor take its share of some damage, and wears it down.
+    /// Returns: the damage left for health.
+    pub fn soak(&mut self, damage: i32) -> i32 {
+        let soaked = ((damage as f32 * ARMOR_SHARE) as i32).min(self.armor);
+
+        self.armor -= soaked;
+        damage - soaked
+    }
+}
+
+impl Component for Player {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// Image drawn as a camera facing billboard where the entity stands.
+#[derive(Copy, Clone, Debug)]
+pub struct Sprite {
+    pub texture: TextureIndex
+}
+
+impl Component for Sprite {
+    type Storage = VecStorage<Self>;
+}
+
+/// Animated sprite sheet, showing the side of the entity that faces the
+/// camera, see `game::sprites`.
+#[derive(Copy, Clone, Debug)]
+pub struct DirectionalSprite {
+    /// Index in the `sheets` of the `TextureRegistry`.
+    pub sheet: usize,
+    /// Index in the `animations` of the sheet.
+    pub animation: usize,
+    /// Seconds since the animation started.
+    pub time: f32
+}
+
+impl Component for DirectionalSprite {
+    type Storage = VecStorage<Self>;
+}
+
+/// Hit points, the entity dies when they run out.
+#[derive(Copy, Clone, Debug)]
+pub struct Health {
+    pub points: i32
+}
+
+impl Component for Health {
+    type Storage = VecStorage<Self>;
+}
+
+/// Hitscan weapon an entity carries, see `game::weapons`.
+#[derive(Copy, Clone, Debug)]
+pub struct Weapon {
+    pub damage: i32,
+    /// How far shots carry, in cells.
+    pub range: f32,
+    /// Seconds between shots.
+    pub cooldown: f32,
+    /// Seconds until the weapon can fire again.
+    pub reload: f32,
+    /// Rounds left, `None` for weapons that never run out.
+    pub ammo: Option<i32>
+}
+
+impl Component for Weapon {
+    type Storage = HashMapStorage<Self>;
+}
+
+/// What an enemy is up to, see `game::enemies`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EnemyState {
+    /// Stands still, waiting for the player to show up.
+    Idle,
+    /// Walks from waypoint to waypoint.
+    Patrol,
+    /// Spotted the player, and takes a moment to react.
+    Alert,
+    /// Runs to where the player was seen last.
+    Chase,
+    /// Winds up a blow at the player.
+    Attack,
+    /// Flinches after being hurt.
+    Pain,
+    Dead
+}
+
+/// Sheet animations an enemy plays, by what it is doing.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct EnemyAnimations {
+    pub stand: usize,
+    pub walk: usize,
+    pub attack: usize,
+    pub pain: usize,
+    pub die: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct Enemy {
+    pub state: EnemyState,
+    /// Seconds spent in `state`.
+    pub state_time: f32,
+    /// Patrol route, walked in a loop.
+    pub waypoints: Vec<Vec3>,
+    /// Index in `waypoints` of the one the enemy walks to.
+    pub next_waypoint: usize,
+    /// Where the enemy last saw the player.
+    pub last_seen: Option<Vec3>,
+    /// Way the enemy follows around the walls, if any.
+    pub route: Option<Route>,
+    pub animations: EnemyAnimations
+}
+
+impl Component for Enemy {
+    type Storage = VecStorage<Self>;
+}
+
+/// Tags the entity the camera sits on.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera {
+    /// Height of the eyes above the floor.
+    pub height: f32
+}
+
+impl Component for Camera {
+    type Storage = HashMapStorage<Self>;
+}
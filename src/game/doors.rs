@@ -0,0 +1,320 @@
+//! Sliding doors.
+//!
+//! A door cell stays `Door`, and therefore solid, while its panel moves; it
+//! only becomes a walkable `OpenDoor` once the panel slid all the way into
+//! the wall.
+
+use glm::Vec3;
+use specs::{ Join, WorldExt };
+use crate::game::model::{
+    Game, System, DungeonFloor, DoorState, DoorMotion,
+    DungeonCell::{ Door, OpenDoor, Wall }
+};
+use crate::game::collision::overlaps_cell;
+use crate::game::components::{ Position, Collider };
+
+/// Seconds it takes a panel to slide fully open or closed.
+pub const SLIDE_TIME: f32 = 1.0;
+/// Seconds an open door waits before closing on its own.
+pub const CLOSE_DELAY: f32 = 4.0;
+/// How far in front of the user a door can be reached, in cells.
+pub const USE_REACH: f32 = 1.0;
+
+/// Opens, or closes, the door in front of the user.
+/// * `pos`: position of the user.
+/// * `direction`: facing of the user.
+/// * `blockers`: circles, as center and radius, that keep an open door open.
+/// Returns: `true` if a door was reached.
+pub fn use_door(
+    floor: &mut DungeonFloor, pos: Vec3, direction: Vec3, blockers: &[(Vec3, f32)]
+) -> bool {
+    let target = pos + direction * USE_REACH;
+    let x = (target.x + 0.5).floor() as i32;
+    let y = (target.y + 0.5).floor() as i32;
+    let cell = match floor.cell(x, y) {
+        Some(c) => c,
+        None => return false
+    };
+    let index = x as usize + y as usize * floor.width;
+
+    match cell {
+        Door => open(floor, index),
+        OpenDoor => {
+            if !is_blocked(x, y, blockers) {
+                let door = floor.doors.entry(index).or_insert_with(open_door);
+                door.motion = DoorMotion::Closing;
+                floor.set_cell(index, Door);
+            }
+        }
+        _ => return false
+    }
+
+    true
+}
+
+/// Starts sliding open the closed door at `index`. Doors already opening,
+/// or open, are left alone.
+pub fn open(floor: &mut DungeonFloor, index: usize) {
+    if floor.cells[index] != Door {
+        return;
+    }
+
+    let door = floor.doors.entry(index).or_insert_with(closed_door);
+
+    if door.motion == DoorMotion::Closed || door.motion == DoorMotion::Closing {
+        door.motion = DoorMotion::Opening;
+    }
+}
+
+/// Advances every door that has been used, bumping `door_revision` if any
+/// panel moved.
+/// * `blockers`: circles, as center and radius, that keep an open door open.
+/// * `dt`: elapsed seconds.
+pub fn update(floor: &mut DungeonFloor, blockers: &[(Vec3, f32)], dt: f32) {
+    let width = floor.width;
+    let mut changes = Vec::new();
+    let mut moved = false;
+
+    for (&index, door) in floor.doors.iter_mut() {
+        match door.motion {
+            DoorMotion::Opening => {
+                door.progress += dt / SLIDE_TIME;
+                moved = true;
+
+                if door.progress >= 1.0 {
+                    door.progress = 1.0;
+                    door.motion = DoorMotion::Open;
+                    door.open_time = 0.0;
+                    changes.push((index, OpenDoor));
+                }
+            }
+            DoorMotion::Open => {
+                door.open_time += dt;
+
+                let x = (index % width) as i32;
+                let y = (index / width) as i32;
+
+                if door.open_time >= CLOSE_DELAY && !is_blocked(x, y, blockers) {
+                    door.motion = DoorMotion::Closing;
+                    changes.push((index, Door));
+                }
+            }
+            DoorMotion::Closing => {
+                door.progress -= dt / SLIDE_TIME;
+                moved = true;
+
+                if door.progress <= 0.0 {
+                    door.progress = 0.0;
+                    door.motion = DoorMotion::Closed;
+                }
+            }
+            DoorMotion::Closed => { }
+        }
+    }
+
+    for (index, cell) in changes {
+        floor.set_cell(index, cell);
+    }
+
+    if moved {
+        floor.door_revision += 1;
+    }
+}
+
+/// Lets the player use doors, and moves the doors in use. Anything that
+/// collides keeps doors from closing on it.
+pub struct DoorSystem;
+
+impl System for DoorSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        let blockers: Vec<(Vec3, f32)> = (
+            &game.world.read_storage::<Position>(),
+            &game.world.read_storage::<Collider>()
+        ).join()
+            .map(|(pos, collider)| (pos.0, collider.radius))
+            .collect();
+
+        if game.input.use_pressed {
+            let pos = game.player_position();
+            let direction = game.player_facing().direction;
+
+            use_door(&mut game.current_map, pos, direction, &blockers);
+        }
+
+        update(&mut game.current_map, &blockers, dt);
+    }
+}
+
+/// How far the panel of the door at `index` slid open, from 0 to 1.
+pub fn progress(floor: &DungeonFloor, index: usize) -> f32 {
+    match floor.doors.get(&index) {
+        Some(door) => door.progress,
+        None => if floor.cells[index] == OpenDoor { 1.0 } else { 0.0 }
+    }
+}
+
+/// Tells whether the door at column `x` and row `y` sits between walls on
+/// its left and right, so that its panel spans the x axis. Otherwise the
+/// panel spans the y axis.
+pub fn spans_x(floor: &DungeonFloor, x: i32, y: i32) -> bool {
+    matches!(floor.cell(x - 1, y), Some(Wall(_)))
+        && matches!(floor.cell(x + 1, y), Some(Wall(_)))
+}
+
+fn is_blocked(x: i32, y: i32, blockers: &[(Vec3, f32)]) -> bool {
+    blockers.iter().any(|(pos, radius)| overlaps_cell(*pos, *radius, x, y))
+}
+
+fn closed_door() -> DoorState {
+    DoorState { progress: 0.0, motion: DoorMotion::Closed, open_time: 0.0 }
+}
+
+fn open_door() -> DoorState {
+    DoorState { progress: 1.0, motion: DoorMotion::Open, open_time: 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::collision::PLAYER_RADIUS;
+    use crate::game::model::DungeonCell::{ self, Floor };
+
+    const W: DungeonCell = Wall(0);
+    const F: DungeonCell = Floor(1);
+    /// Seconds per update, exact in binary so that the steps add up.
+    const DT: f32 = 0.25;
+    /// The door of `corridor`, at column 2 of row 1.
+    const DOOR: usize = 7;
+
+    /// An east-west corridor closed by a door in the middle.
+    fn corridor() -> DungeonFloor {
+        DungeonFloor::new(5, 3, vec![
+            W, W, W, W, W,
+            W, F, Door, F, W,
+            W, W, W, W, W
+        ])
+    }
+
+    /// Position of a user standing west of the door.
+    fn west() -> Vec3 {
+        Vec3::new(1.0, 1.0, 0.0)
+    }
+
+    fn run(floor: &mut DungeonFloor, blockers: &[(Vec3, f32)], seconds: f32) {
+        for _ in 0..(seconds / DT).round() as usize {
+            update(floor, blockers, DT);
+        }
+    }
+
+    fn motion(floor: &DungeonFloor) -> DoorMotion {
+        floor.doors[&DOOR].motion
+    }
+
+    /// Opens the door of `corridor` all the way.
+    fn opened() -> DungeonFloor {
+        let mut floor = corridor();
+
+        use_door(&mut floor, west(), Vec3::new(1.0, 0.0, 0.0), &[]);
+        run(&mut floor, &[], SLIDE_TIME);
+        floor
+    }
+
+    #[test]
+    fn uses_the_door_in_front() {
+        let mut floor = corridor();
+
+        assert!(!use_door(&mut floor, west(), Vec3::new(-1.0, 0.0, 0.0), &[]));
+        assert!(floor.doors.is_empty());
+
+        assert!(use_door(&mut floor, west(), Vec3::new(1.0, 0.0, 0.0), &[]));
+        assert_eq!(motion(&floor), DoorMotion::Opening);
+        // Solid until the panel is out of the way.
+        assert_eq!(floor.cells[DOOR], Door);
+    }
+
+    #[test]
+    fn slides_open() {
+        let mut floor = corridor();
+
+        use_door(&mut floor, west(), Vec3::new(1.0, 0.0, 0.0), &[]);
+        run(&mut floor, &[], SLIDE_TIME / 2.0);
+        assert_eq!(progress(&floor, DOOR), 0.5);
+        assert_eq!(floor.cells[DOOR], Door);
+
+        let revision = floor.door_revision;
+        run(&mut floor, &[], SLIDE_TIME / 2.0);
+        assert_eq!(progress(&floor, DOOR), 1.0);
+        assert!(floor.door_revision > revision);
+        assert_eq!(motion(&floor), DoorMotion::Open);
+        assert_eq!(floor.cells[DOOR], OpenDoor);
+    }
+
+    #[test]
+    fn closes_when_used_again() {
+        let mut floor = opened();
+
+        assert!(use_door(&mut floor, west(), Vec3::new(1.0, 0.0, 0.0), &[]));
+        assert_eq!(motion(&floor), DoorMotion::Closing);
+        assert_eq!(floor.cells[DOOR], Door);
+
+        run(&mut floor, &[], SLIDE_TIME);
+        assert_eq!(progress(&floor, DOOR), 0.0);
+        assert_eq!(motion(&floor), DoorMotion::Closed);
+    }
+
+    #[test]
+    fn closes_on_its_own() {
+        let mut floor = opened();
+
+        let revision = floor.door_revision;
+        run(&mut floor, &[], CLOSE_DELAY - DT);
+        assert_eq!(floor.cells[DOOR], OpenDoor);
+        // Waiting open moves nothing.
+        assert_eq!(floor.door_revision, revision);
+
+        run(&mut floor, &[], DT);
+        assert_eq!(motion(&floor), DoorMotion::Closing);
+        assert_eq!(floor.cells[DOOR], Door);
+    }
+
+    #[test]
+    fn stays_open_while_blocked() {
+        let mut floor = opened();
+        let in_the_doorway = [(Vec3::new(2.0, 1.0, 0.0), PLAYER_RADIUS)];
+
+        run(&mut floor, &in_the_doorway, CLOSE_DELAY * 2.0);
+        assert_eq!(floor.cells[DOOR], OpenDoor);
+
+        use_door(&mut floor, west(), Vec3::new(1.0, 0.0, 0.0), &in_the_doorway);
+        assert_eq!(motion(&floor), DoorMotion::Open);
+
+        run(&mut floor, &[], DT);
+        assert_eq!(floor.cells[DOOR], Door);
+    }
+
+    #[test]
+    fn stays_open_on_large_colliders() {
+        let mut floor = opened();
+        // Half a cell east of the doorway.
+        let beside = Vec3::new(3.0, 1.0, 0.0);
+
+        run(&mut floor, &[(beside, 0.8)], CLOSE_DELAY * 2.0);
+        assert_eq!(floor.cells[DOOR], OpenDoor);
+
+        run(&mut floor, &[(beside, PLAYER_RADIUS)], DT);
+        assert_eq!(floor.cells[DOOR], Door);
+    }
+
+    #[test]
+    fn panels_span_the_doorway() {
+        let across = corridor();
+        let along = DungeonFloor::new(3, 3, vec![
+            W, F, W,
+            W, Door, W,
+            W, F, W
+        ]);
+
+        assert!(!spans_x(&across, 2, 1));
+        assert!(spans_x(&along, 1, 1));
+    }
+}
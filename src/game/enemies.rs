@@ -0,0 +1,594 @@
+//! Enemies, what they see and what they do about it.
+//!
+//! Every enemy runs a small state machine:
+//! * `Idle` or `Patrol` until the player walks into sight, then `Alert`;
+//! * `Alert` for `REACTION_TIME`, then `Chase`;
+//! * `Chase` to where the player was seen last, `Attack` once in reach,
+//!   or back to `Patrol` or `Idle` when the trail goes cold;
+//! * `Attack` for `ATTACK_TIME`, then `Chase` again;
+//! * `Pain` for `PAIN_TIME` when hurt, then `Chase`, or `Dead`.
+//!
+//! Out of sight of where they are going, enemies find their way around the
+//! walls, and open the doors on it.
+
+use glm::Vec3;
+use specs::{ Builder, Entity, Join, WorldExt };
+use crate::game::{
+    components::{
+        Position, Velocity, Facing, Collider, Health, DirectionalSprite,
+        Enemy, EnemyState, EnemyAnimations, Player
+    },
+    doors,
+    model::{ DungeonFloor, DungeonCell, Game, System },
+    pathfinding::{ cell_of, center_of, find_path, Cell, FlowField, Route },
+    raycast::line_of_sight
+};
+
+/// Radius of the collision circle of enemies, in cells.
+pub const ENEMY_RADIUS: f32 = 0.3;
+pub const ENEMY_HEALTH: i32 = 25;
+/// Walking speed on patrol, in cells per second.
+pub const PATROL_SPEED: f32 = 1.5;
+/// Running speed after the player, in cells per second.
+pub const CHASE_SPEED: f32 = 2.5;
+/// Cosine of half the field of view of enemies that have not spotted the
+/// player yet. Once aware of the player, they see all around.
+pub const VISION_COS: f32 = 0.5;
+/// Seconds between spotting the player and running after them.
+pub const REACTION_TIME: f32 = 0.5;
+/// How close to the player an enemy attacks from, in cells.
+pub const ATTACK_REACH: f32 = 1.0;
+/// Seconds an attack takes to land.
+pub const ATTACK_TIME: f32 = 0.5;
+pub const ATTACK_DAMAGE: i32 = 10;
+/// Seconds an enemy flinches after being hurt.
+pub const PAIN_TIME: f32 = 0.3;
+
+/// How many cells of walking a closed door is worth to enemies.
+pub const DOOR_COST: f32 = 3.0;
+
+/// How close to a waypoint counts as there.
+const ARRIVAL_DISTANCE: f32 = 0.1;
+
+/// Adds an idle enemy to the game.
+/// * `pos`: where the enemy stands.
+/// * `angle`: where the enemy looks.
+/// * `waypoints`: patrol route, empty to stand guard.
+/// Returns: the new entity.
+pub fn spawn(game: &mut Game, pos: Vec3, angle: f32, waypoints: Vec<Vec3>) -> Entity {
+    game.world.create_entity()
+        .with(Position(pos))
+        .with(Velocity(Vec3::new(0.0, 0.0, 0.0)))
+        .with(Facing::new(angle))
+        .with(Collider { radius: ENEMY_RADIUS })
+        .with(Health { points: ENEMY_HEALTH })
+        .with(Enemy {
+            state: EnemyState::Idle,
+            state_time: 0.0,
+            waypoints,
+            next_waypoint: 0,
+            last_seen: None,
+            route: None,
+            animations: EnemyAnimations::default()
+        })
+        .build()
+}
+
+/// Takes hit points from an entity. Hurt enemies flinch and turn on the
+/// player, or die and stop getting in the way when out of health.
+pub fn hurt(game: &mut Game, entity: Entity, damage: i32) {
+    let player_pos = game.player_position();
+    let mut healths = game.world.write_storage::<Health>();
+    let mut enemies = game.world.write_storage::<Enemy>();
+    let health = match healths.get_mut(entity) {
+        Some(h) => h,
+        None => return
+    };
+
+    health.points = (health.points - damage).max(0);
+
+    let enemy = match enemies.get_mut(entity) {
+        Some(e) if e.state != EnemyState::Dead => e,
+        _ => return
+    };
+
+    if health.points == 0 {
+        enter(enemy, EnemyState::Dead);
+        game.world.write_storage::<Collider>().remove(entity);
+
+        if let Some(velocity) = game.world.write_storage::<Velocity>().get_mut(entity) {
+            velocity.0 = Vec3::new(0.0, 0.0, 0.0);
+        }
+    } else {
+        enter(enemy, EnemyState::Pain);
+        enemy.last_seen = Some(player_pos);
+    }
+}
+
+/// Runs the state machines of the enemies, and lands their attacks on the
+/// player.
+#[derive(Default)]
+pub struct EnemyAiSystem {
+    /// Leads the enemies that chase the player where the player stands,
+    /// laid again whenever the player changes cell.
+    flow: Option<FlowField>
+}
+
+/// What enemies know of the world around them during a tick.
+struct Surroundings<'a> {
+    floor: &'a DungeonFloor,
+    /// Where the player stands.
+    target: Vec3,
+    flow: Option<&'a FlowField>
+}
+
+/// What an enemy does to the world during a tick.
+#[derive(Default)]
+struct Actions {
+    /// Dealt to the player.
+    damage: i32,
+    /// Index of a door cell to open.
+    door: Option<usize>
+}
+
+/// How following a way went.
+enum Travel {
+    Moving,
+    /// There, or there is no way.
+    Arrived,
+    /// Waiting for the door at this cell index.
+    Door(usize)
+}
+
+impl System for EnemyAiSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        let target = game.player_position();
+        let floor = &game.current_map;
+        let positions = game.world.read_storage::<Position>();
+        let mut facings = game.world.write_storage::<Facing>();
+        let mut velocities = game.world.write_storage::<Velocity>();
+        let mut enemies = game.world.write_storage::<Enemy>();
+        let mut sprites = game.world.write_storage::<DirectionalSprite>();
+        let mut damage = 0;
+        let mut opened = Vec::new();
+        let hunted = cell_of(target);
+        let chasing = (&enemies).join().any(|enemy| enemy.state == EnemyState::Chase);
+        let stale = match &self.flow {
+            Some(flow) => !flow.leads_to(floor, hunted),
+            None => true
+        };
+
+        if chasing && stale {
+            self.flow = Some(FlowField::toward(floor, hunted, Some(DOOR_COST)));
+        }
+
+        let surroundings = Surroundings { floor, target, flow: self.flow.as_ref() };
+
+        for (enemy, pos, facing, velocity, sprite) in (
+            &mut enemies, &positions, &mut facings, &mut velocities, (&mut sprites).maybe()
+        ).join() {
+            let actions = think(enemy, pos.0, facing, velocity, &surroundings, dt);
+
+            damage += actions.damage;
+            opened.extend(actions.door);
+
+            if let Some(sprite) = sprite {
+                let animation = animation(enemy);
+
+                if sprite.animation != animation {
+                    sprite.animation = animation;
+                    sprite.time = 0.0;
+                }
+            }
+        }
+
+        for index in opened {
+            doors::open(&mut game.current_map, index);
+        }
+
+        if damage > 0 {
+            let damage = match game.world.write_storage::<Player>().get_mut(game.player) {
+                Some(player) => player.soak(damage),
+                None => damage
+            };
+
+            if let Some(health) = game.world.write_storage::<Health>().get_mut(game.player) {
+                health.points = (health.points - damage).max(0);
+            }
+        }
+    }
+}
+
+/// Runs the state machine of an enemy for a tick.
+fn think(
+    enemy: &mut Enemy, pos: Vec3, facing: &mut Facing, velocity: &mut Velocity,
+    surroundings: &Surroundings, dt: f32
+) -> Actions {
+    use EnemyState::*;
+
+    let target = surroundings.target;
+    let floor = surroundings.floor;
+    let mut actions = Actions::default();
+
+    enemy.state_time += dt;
+    velocity.0 = Vec3::new(0.0, 0.0, 0.0);
+
+    if enemy.state == Dead {
+        return actions;
+    }
+
+    let aware = matches!(enemy.state, Alert | Chase | Attack | Pain);
+    let sees = sees(floor, pos, facing.direction, target, aware);
+    let in_reach = sees && flat(target - pos).norm() <= ATTACK_REACH;
+
+    if sees {
+        enemy.last_seen = Some(target);
+    }
+
+    match enemy.state {
+        Idle | Patrol if sees => enter(enemy, Alert),
+        Idle => {
+            if !enemy.waypoints.is_empty() {
+                enter(enemy, Patrol);
+            }
+        }
+        Patrol => {
+            if let Some(goal) = enemy.waypoints.get(enemy.next_waypoint).copied() {
+                match travel(enemy, pos, goal, PATROL_SPEED, surroundings, facing, velocity) {
+                    Travel::Arrived => {
+                        enemy.next_waypoint = (enemy.next_waypoint + 1) % enemy.waypoints.len();
+                    }
+                    Travel::Door(index) => actions.door = Some(index),
+                    Travel::Moving => { }
+                }
+            }
+        }
+        Alert => {
+            face(facing, target - pos);
+
+            if enemy.state_time >= REACTION_TIME {
+                enter(enemy, Chase);
+            }
+        }
+        Chase => {
+            if in_reach {
+                enter(enemy, Attack);
+            } else {
+                let arrived = match enemy.last_seen {
+                    Some(goal) => match travel(
+                        enemy, pos, goal, CHASE_SPEED, surroundings, facing, velocity
+                    ) {
+                        Travel::Arrived => true,
+                        Travel::Door(index) => {
+                            actions.door = Some(index);
+                            false
+                        }
+                        Travel::Moving => false
+                    },
+                    None => true
+                };
+
+                if arrived && !sees {
+                    enemy.last_seen = None;
+                    give_up(enemy);
+                }
+            }
+        }
+        Attack => {
+            face(facing, target - pos);
+
+            if enemy.state_time >= ATTACK_TIME {
+                enter(enemy, Chase);
+
+                if in_reach {
+                    actions.damage = ATTACK_DAMAGE;
+                }
+            }
+        }
+        Pain => {
+            if enemy.state_time >= PAIN_TIME {
+                enter(enemy, Chase);
+            }
+        }
+        Dead => { }
+    }
+
+    actions
+}
+
+/// Tells whether an enemy sees the player.
+/// * `aware`: the enemy knows about the player, and looks all around.
+fn sees(floor: &DungeonFloor, pos: Vec3, direction: Vec3, target: Vec3, aware: bool) -> bool {
+    let to_target = flat(target - pos);
+    let distance = to_target.norm();
+
+    if distance < 1e-6 {
+        return true;
+    }
+
+    (aware || glm::dot(&direction, &(to_target / distance)) >= VISION_COS)
+        && line_of_sight(floor, pos, target)
+}
+
+/// Heads towards `goal`, straight when in sight, otherwise along the flow
+/// field when it leads there, or along a route of the enemy's own.
+fn travel(
+    enemy: &mut Enemy, pos: Vec3, goal: Vec3, speed: f32, surroundings: &Surroundings,
+    facing: &mut Facing, velocity: &mut Velocity
+) -> Travel {
+    let floor = surroundings.floor;
+    let here = cell_of(pos);
+    let there = cell_of(goal);
+
+    if here == there || line_of_sight(floor, pos, goal) {
+        enemy.route = None;
+
+        return if walk(pos, goal, speed, facing, velocity) {
+            Travel::Arrived
+        } else {
+            Travel::Moving
+        };
+    }
+
+    let next = match surroundings.flow {
+        Some(flow) if flow.leads_to(floor, there) => flow.next_step(floor, here),
+        _ => route_step(enemy, floor, here, there)
+    };
+    let next = match next {
+        Some(cell) => cell,
+        None => return Travel::Arrived
+    };
+
+    if floor.cell(next.0, next.1) == Some(DungeonCell::Door) {
+        face(facing, center_of(next) - pos);
+        return Travel::Door(next.0 as usize + next.1 as usize * floor.width);
+    }
+
+    walk(pos, center_of(next), speed, facing, velocity);
+    Travel::Moving
+}
+
+/// Next cell on the route of an enemy to `there`, planned again when the
+/// goal or the floor changed, or the enemy strayed from it.
+fn route_step(enemy: &mut Enemy, floor: &DungeonFloor, here: Cell, there: Cell) -> Option<Cell> {
+    if let Some(route) = enemy.route.as_mut() {
+        while route.steps.last() == Some(&here) {
+            route.steps.pop();
+        }
+    }
+
+    let on_track = match &enemy.route {
+        Some(route) => route.goal == there && route.revision == floor.revision
+            && match route.steps.last() {
+                Some(next) => (next.0 - here.0).abs() <= 1 && (next.1 - here.1).abs() <= 1,
+                None => false
+            },
+        None => false
+    };
+
+    if !on_track {
+        enemy.route = find_path(floor, here, there, Some(DOOR_COST)).map(|mut steps| {
+            steps.reverse();
+            Route { goal: there, revision: floor.revision, steps }
+        });
+    }
+
+    enemy.route.as_ref()?.steps.last().copied()
+}
+
+/// Heads towards `goal`.
+/// Returns: `true` once there.
+fn walk(pos: Vec3, goal: Vec3, speed: f32, facing: &mut Facing, velocity: &mut Velocity) -> bool {
+    let delta = flat(goal - pos);
+
+    if delta.norm() <= ARRIVAL_DISTANCE {
+        return true;
+    }
+
+    face(facing, delta);
+    velocity.0 = delta.normalize() * speed;
+
+    false
+}
+
+fn face(facing: &mut Facing, towards: Vec3) {
+    if flat(towards).norm() > 1e-6 {
+        *facing = Facing::new(towards.y.atan2(towards.x));
+    }
+}
+
+fn give_up(enemy: &mut Enemy) {
+    if enemy.waypoints.is_empty() {
+        enter(enemy, EnemyState::Idle);
+    } else {
+        enter(enemy, EnemyState::Patrol);
+    }
+}
+
+fn enter(enemy: &mut Enemy, state: EnemyState) {
+    enemy.state = state;
+    enemy.state_time = 0.0;
+}
+
+fn animation(enemy: &Enemy) -> usize {
+    match enemy.state {
+        EnemyState::Idle | EnemyState::Alert => enemy.animations.stand,
+        EnemyState::Patrol | EnemyState::Chase => enemy.animations.walk,
+        EnemyState::Attack => enemy.animations.attack,
+        EnemyState::Pain => enemy.animations.pain,
+        EnemyState::Dead => enemy.animations.die
+    }
+}
+
+fn flat(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, v.y, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use crate::game::input::InputState;
+    use crate::uglythings::build_experimental_game;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn run(game: &mut Game, ticks: usize) {
+        for _ in 0..ticks {
+            game.tick(DT, &InputState::default());
+        }
+    }
+
+    fn enemy(game: &Game, entity: Entity) -> Enemy {
+        game.world.read_storage::<Enemy>().get(entity).unwrap().clone()
+    }
+
+    fn position(game: &Game, entity: Entity) -> Vec3 {
+        game.world.read_storage::<Position>().get(entity).unwrap().0
+    }
+
+    fn player_health(game: &Game) -> i32 {
+        game.world.read_storage::<Health>().get(game.player).unwrap().points
+    }
+
+    fn chase(game: &mut Game, entity: Entity, goal: Vec3) {
+        let mut enemies = game.world.write_storage::<Enemy>();
+        let enemy = enemies.get_mut(entity).unwrap();
+
+        enemy.state = EnemyState::Chase;
+        enemy.last_seen = Some(goal);
+    }
+
+    #[test]
+    fn chases_and_reaches_the_player() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(4.0, 6.0, 0.0), (2.0f32).atan2(-3.0), Vec::new());
+
+        run(&mut game, 10);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Alert);
+
+        run(&mut game, 290);
+
+        let distance = glm::distance(&position(&game, guard), &game.player_position());
+        assert!(distance <= ATTACK_REACH + 0.05, "stopped {} cells away", distance);
+        assert!(player_health(&game) < 100);
+    }
+
+    #[test]
+    fn armor_soaks_part_of_the_blows() {
+        let mut game = build_experimental_game();
+
+        game.world.write_storage::<Player>().get_mut(game.player).unwrap().armor = 30;
+        spawn(&mut game, Vec3::new(2.0, 8.0, 0.0), PI, Vec::new());
+        run(&mut game, 300);
+
+        let armor = game.world.read_storage::<Player>().get(game.player).unwrap().armor;
+        let blows = (30 - armor) / 3;
+
+        assert!(blows > 0);
+        assert_eq!(player_health(&game), 100 - blows * (ATTACK_DAMAGE - 3));
+    }
+
+    #[test]
+    fn does_not_see_through_walls() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(5.0, 5.0, 0.0), PI, Vec::new());
+
+        // Hidden behind the pillar at (3, 5).
+        game.world.write_storage::<Position>()
+            .insert(game.player, Position(Vec3::new(1.0, 5.0, 0.0))).unwrap();
+        run(&mut game, 60);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Idle);
+
+        game.world.write_storage::<Position>()
+            .insert(game.player, Position(Vec3::new(2.0, 7.0, 0.0))).unwrap();
+        run(&mut game, 1);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Alert);
+    }
+
+    #[test]
+    fn patrols_between_waypoints() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(5.0, 3.0, 0.0), -PI / 2.0, vec![
+            Vec3::new(5.0, 3.0, 0.0), Vec3::new(5.0, 1.0, 0.0)
+        ]);
+
+        // Out of sight, in the east room.
+        game.world.write_storage::<Position>()
+            .insert(game.player, Position(Vec3::new(8.0, 5.0, 0.0))).unwrap();
+        run(&mut game, 60);
+
+        let guard_state = enemy(&game, guard);
+        assert_eq!(guard_state.state, EnemyState::Patrol);
+        assert_eq!(guard_state.next_waypoint, 1);
+        assert!(position(&game, guard).y < 2.0);
+
+        let mut ticks = 0;
+
+        while enemy(&game, guard).next_waypoint == 1 {
+            run(&mut game, 1);
+            ticks += 1;
+            assert!(ticks < 60, "never reached the second waypoint");
+        }
+
+        assert!(position(&game, guard).y < 1.2);
+
+        run(&mut game, 90);
+        assert_eq!(enemy(&game, guard).next_waypoint, 1);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Patrol);
+    }
+
+    #[test]
+    fn finds_the_way_through_doors() {
+        let mut game = build_experimental_game();
+        let width = game.current_map.width;
+
+        // Shut the east room.
+        game.current_map.set_cell(6 + 4 * width, DungeonCell::Door);
+        game.current_map.set_cell(6 + 5 * width, DungeonCell::Door);
+
+        let guard = spawn(&mut game, Vec3::new(8.0, 5.0, 0.0), PI, Vec::new());
+
+        // Heard something in the north nook.
+        chase(&mut game, guard, Vec3::new(4.5, 1.0, 0.0));
+        run(&mut game, 30);
+        assert!(position(&game, guard).x > 6.5);
+        assert!(!game.current_map.doors.is_empty());
+
+        run(&mut game, 270);
+        assert!(position(&game, guard).x < 6.0, "stuck at {:?}", position(&game, guard));
+    }
+
+    #[test]
+    fn crowds_follow_the_flow_field() {
+        let mut game = build_experimental_game();
+        let guards: Vec<_> = [(8.0, 4.0), (8.0, 5.0), (4.5, 1.0)].iter()
+            .map(|(x, y)| spawn(&mut game, Vec3::new(*x, *y, 0.0), 0.0, Vec::new()))
+            .collect();
+        let target = game.player_position();
+
+        for guard in guards.iter() {
+            chase(&mut game, *guard, target);
+        }
+
+        run(&mut game, 480);
+
+        for guard in guards.iter() {
+            let distance = glm::distance(&position(&game, *guard), &target);
+            assert!(distance <= ATTACK_REACH + 0.5, "stopped {} cells away", distance);
+        }
+    }
+
+    #[test]
+    fn flinches_then_dies() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(5.0, 1.0, 0.0), 0.0, Vec::new());
+
+        hurt(&mut game, guard, 10);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Pain);
+
+        hurt(&mut game, guard, ENEMY_HEALTH);
+        assert_eq!(enemy(&game, guard).state, EnemyState::Dead);
+        assert!(game.world.read_storage::<Collider>().get(guard).is_none());
+    }
+}
@@ -0,0 +1,102 @@
+//! Remembers the cells the player has seen, for the automap.
+
+use specs::WorldExt;
+use glm::Vec3;
+use crate::game::{
+    components::Player,
+    model::{ DungeonFloor, Game, System },
+    raycast::cast_through
+};
+
+/// How far the player sees, in cells.
+pub const SIGHT_RANGE: f32 = 32.0;
+/// Rays cast all around to find what the player sees.
+const SIGHT_RAYS: usize = 720;
+/// How far the player moves before looking around again, in cells.
+const LOOK_STEP: f32 = 0.25;
+
+/// Marks the cells in sight of a point, all around it, up to the solid
+/// cells that block the view.
+/// * `explored`: cells seen so far, by cell index, grown to the size of the
+///   map when shorter.
+pub fn look_around(floor: &DungeonFloor, pos: Vec3, explored: &mut Vec<bool>) {
+    if explored.len() < floor.cells.len() {
+        explored.resize(floor.cells.len(), false);
+    }
+
+    for ray in 0..SIGHT_RAYS {
+        let angle = ray as f32 * std::f32::consts::PI * 2.0 / SIGHT_RAYS as f32;
+        let direction = Vec3::new(angle.cos(), angle.sin(), 0.0);
+
+        cast_through(floor, pos, direction, SIGHT_RANGE, |x, y| {
+            if floor.cell(x, y).is_some() {
+                explored[x as usize + y as usize * floor.width] = true;
+            }
+        });
+    }
+}
+
+/// Marks what the player sees as they move around, and as doors open.
+#[derive(Default)]
+pub struct ExplorationSystem {
+    /// Where the player looked around last, and the revision of the map
+    /// then.
+    last_look: Option<(Vec3, u64)>
+}
+
+impl System for ExplorationSystem {
+    fn update(&mut self, game: &mut Game, _dt: f32) {
+        let pos = game.player_position();
+        let revision = game.current_map.revision;
+        let stale = match self.last_look {
+            Some((last, last_revision)) => {
+                last_revision != revision || glm::distance(&last, &pos) >= LOOK_STEP
+            }
+            None => true
+        };
+
+        if !stale {
+            return;
+        }
+
+        self.last_look = Some((pos, revision));
+
+        if let Some(player) = game.world.write_storage::<Player>().get_mut(game.player) {
+            look_around(&game.current_map, pos, &mut player.explored);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::model::DungeonCell::{ self, Wall, Floor, Door, OpenDoor };
+
+    const W: DungeonCell = Wall(0);
+    const F: DungeonCell = Floor(1);
+
+    #[test]
+    fn closed_doors_block_the_view() {
+        let mut floor = DungeonFloor::new(7, 3, vec![
+            W, W, W, W, W, W, W,
+            W, F, F, Door, F, F, W,
+            W, W, W, W, W, W, W
+        ]);
+        let mut explored = Vec::new();
+        // Cells of the corridor, on row 1.
+        let seen = |explored: &Vec<bool>, x: usize| explored[x + 7];
+
+        look_around(&floor, Vec3::new(1.0, 1.0, 0.0), &mut explored);
+
+        assert_eq!(explored.len(), 21);
+        assert!(seen(&explored, 2) && seen(&explored, 3));
+        assert!(!seen(&explored, 4));
+        // Walls along the corridor, and behind the player.
+        assert!(explored[2] && explored[7]);
+
+        floor.set_cell(10, OpenDoor);
+        look_around(&floor, Vec3::new(1.0, 1.0, 0.0), &mut explored);
+
+        assert!(seen(&explored, 5) && seen(&explored, 6));
+    }
+}
@@ -0,0 +1,46 @@
+//! Player input, as seen by the simulation.
+
+use serde::{ Serialize, Deserialize };
+
+/// Things the player can ask for, whatever device they come from.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    Forward,
+    Back,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Use,
+    Fire,
+    /// Shows or hides the automap.
+    Map,
+    /// Switches the automap between its corner and the whole screen.
+    MapFullScreen,
+    MapZoomIn,
+    MapZoomOut,
+    MapPanUp,
+    MapPanDown,
+    MapPanLeft,
+    MapPanRight,
+    /// Centers the automap back on the player.
+    MapRecenter
+}
+
+/// What the player asks for during a tick. Axes range from -1 to 1.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct InputState {
+    /// Walk forward (positive) or backward (negative).
+    pub forward: f32,
+    /// Strafe right (positive) or left (negative).
+    pub strafe: f32,
+    /// Turn left (positive) or right (negative).
+    pub turn: f32,
+    /// Radians to turn left (positive) or right (negative) on top of
+    /// `turn`, as with the mouse.
+    pub yaw: f32,
+    /// The use key went down since the previous tick.
+    pub use_pressed: bool,
+    /// The fire key is held down.
+    pub fire: bool
+}
@@ -0,0 +1,491 @@
+//! Loads dungeon levels from RON files.
+
+use std::path::Path;
+use serde::Deserialize;
+use glm::Vec3;
+use specs::{ Builder, WorldExt };
+use crate::game::{
+    components::{ Position, Facing, Sprite, DirectionalSprite, Enemy, EnemyAnimations },
+    enemies,
+    model::{ Game, DungeonFloor, DungeonCell, CellTextures, TextureIndex },
+    textures::TextureRegistry
+};
+
+/// On-disk layout of a level. `cells` is a list of rows, top to bottom, so
+/// the file reads like the map it describes.
+#[derive(Deserialize)]
+struct LevelRon {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<DungeonCell>>,
+    player_start: (f32, f32),
+    player_angle: f32,
+    #[serde(default)]
+    textures: Vec<TextureOverrideRon>,
+    #[serde(default)]
+    sprites: Vec<SpriteRon>,
+    #[serde(default)]
+    actors: Vec<ActorRon>
+}
+
+/// Floor and ceiling textures of a single walkable cell.
+#[derive(Deserialize)]
+struct TextureOverrideRon {
+    x: usize,
+    y: usize,
+    floor: Option<TextureIndex>,
+    ceiling: Option<TextureIndex>
+}
+
+/// Decoration standing in the dungeon.
+#[derive(Deserialize)]
+struct SpriteRon {
+    x: f32,
+    y: f32,
+    texture: TextureIndex
+}
+
+/// Thing drawn from a sprite sheet, that shows a different side depending
+/// on where it is seen from.
+#[derive(Deserialize)]
+struct ActorRon {
+    x: f32,
+    y: f32,
+    angle: f32,
+    /// Name of the sheet in the texture manifest.
+    sheet: String,
+    /// Animation played standing, enemies look for `walk`, `attack`, `pain`
+    /// and `die` animations too and stand when the sheet lacks them.
+    animation: String,
+    /// Makes the actor an enemy.
+    #[serde(default)]
+    enemy: Option<EnemyRon>
+}
+
+#[derive(Deserialize)]
+struct EnemyRon {
+    /// Patrol route, empty to stand guard.
+    #[serde(default)]
+    waypoints: Vec<(f32, f32)>
+}
+
+/// Loads a level file and builds a new `Game` on it.
+/// * `path`: RON level file, usually under `levels/`.
+/// * `registry`: textures the cells of the level may refer to.
+/// Returns: `Result<Game, String>`.
+pub fn load_level<P: AsRef<Path>>(path: P, registry: &TextureRegistry) -> Result<Game, String> {
+    let source = match std::fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(error) => {
+            return Err(format!("{}: {}", path.as_ref().display(), error));
+        }
+    };
+
+    level_from_str(&source, registry)
+        .map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+}
+
+/// Parses and validates a level from RON source.
+pub fn level_from_str(source: &str, registry: &TextureRegistry) -> Result<Game, String> {
+    let level: LevelRon = ron::de::from_str(source)
+        .map_err(|e| format!("{}", e))?;
+
+    validate(&level, registry)?;
+
+    let (x, y) = level.player_start;
+    let width = level.width;
+    let textures = level.textures.into_iter()
+        .map(|t| (t.x + t.y * width, CellTextures {
+            floor: t.floor,
+            ceiling: t.ceiling
+        }))
+        .collect();
+
+    let mut current_map = DungeonFloor::new(
+        level.width, level.height, level.cells.into_iter().flatten().collect()
+    );
+    current_map.textures = textures;
+
+    let mut game = Game::new(current_map, Vec3::new(x, y, 0.0), level.player_angle);
+
+    for sprite in level.sprites.iter() {
+        game.world.create_entity()
+            .with(Position(Vec3::new(sprite.x, sprite.y, 0.0)))
+            .with(Sprite { texture: sprite.texture })
+            .build();
+    }
+
+    for actor in level.actors.iter() {
+        // Both exist, `validate` made sure of it.
+        let sheet = registry.sheet(&actor.sheet).unwrap();
+        let animation = registry.sheets[sheet].animation(&actor.animation).unwrap();
+        let pos = Vec3::new(actor.x, actor.y, 0.0);
+        let entity = match &actor.enemy {
+            Some(enemy) => {
+                let waypoints = enemy.waypoints.iter()
+                    .map(|(x, y)| Vec3::new(*x, *y, 0.0))
+                    .collect();
+                let entity = enemies::spawn(&mut game, pos, actor.angle, waypoints);
+                let find = |name| registry.sheets[sheet].animation(name).unwrap_or(animation);
+
+                game.world.write_storage::<Enemy>().get_mut(entity).unwrap().animations =
+                    EnemyAnimations {
+                        stand: animation,
+                        walk: find("walk"),
+                        attack: find("attack"),
+                        pain: find("pain"),
+                        die: find("die")
+                    };
+
+                entity
+            }
+            None => game.world.create_entity()
+                .with(Position(pos))
+                .with(Facing::new(actor.angle))
+                .build()
+        };
+
+        game.world.write_storage::<DirectionalSprite>()
+            .insert(entity, DirectionalSprite { sheet, animation, time: 0.0 })
+            .unwrap();
+    }
+
+    Ok(game)
+}
+
+fn validate(level: &LevelRon, registry: &TextureRegistry) -> Result<(), String> {
+    if level.width == 0 || level.height == 0 {
+        return Err(format!("invalid map size {}x{}", level.width, level.height));
+    }
+
+    if level.cells.len() != level.height {
+        return Err(format!(
+            "expected {} rows, found {}", level.height, level.cells.len()
+        ));
+    }
+
+    for (row, cells) in level.cells.iter().enumerate() {
+        if cells.len() != level.width {
+            return Err(format!(
+                "row {}: expected {} columns, found {}",
+                row, level.width, cells.len()
+            ));
+        }
+
+        for (column, cell) in cells.iter().enumerate() {
+            let on_border = row == 0 || column == 0
+                || row == level.height - 1 || column == level.width - 1;
+
+            // Walkable cells on the border would let the player walk off
+            // the map.
+            if on_border && !matches!(cell, DungeonCell::Empty | DungeonCell::Wall(_)) {
+                return Err(format!(
+                    "row {}, column {}: {:?} cell on the map border",
+                    row, column, cell
+                ));
+            }
+
+            match cell {
+                DungeonCell::Wall(texture) | DungeonCell::Floor(texture)
+                    if !registry.contains(*texture) => {
+                    return Err(format!(
+                        "row {}, column {}: unknown texture {}", row, column, texture
+                    ));
+                }
+                _ => { }
+            }
+        }
+    }
+
+    for texture in level.textures.iter() {
+        if texture.x >= level.width || texture.y >= level.height {
+            return Err(format!(
+                "row {}, column {}: texture override outside the map",
+                texture.y, texture.x
+            ));
+        }
+
+        let cell = level.cells[texture.y][texture.x];
+
+        if cell.is_solid() && cell != DungeonCell::Door {
+            return Err(format!(
+                "row {}, column {}: texture override on a {:?} cell",
+                texture.y, texture.x, cell
+            ));
+        }
+
+        for index in texture.floor.iter().chain(texture.ceiling.iter()) {
+            if !registry.contains(*index) {
+                return Err(format!(
+                    "row {}, column {}: unknown texture {}",
+                    texture.y, texture.x, index
+                ));
+            }
+        }
+    }
+
+    for sprite in level.sprites.iter() {
+        let (row, column) = cell_at(level, sprite.x, sprite.y).ok_or_else(|| format!(
+            "sprite ({}, {}) is outside the map", sprite.x, sprite.y
+        ))?;
+        let cell = level.cells[row][column];
+
+        if cell.is_solid() {
+            return Err(format!(
+                "row {}, column {}: sprite on a {:?} cell", row, column, cell
+            ));
+        }
+
+        if !registry.contains(sprite.texture) {
+            return Err(format!(
+                "row {}, column {}: unknown texture {}", row, column, sprite.texture
+            ));
+        }
+    }
+
+    for actor in level.actors.iter() {
+        let (row, column) = cell_at(level, actor.x, actor.y).ok_or_else(|| format!(
+            "actor ({}, {}) is outside the map", actor.x, actor.y
+        ))?;
+        let cell = level.cells[row][column];
+
+        if cell.is_solid() {
+            return Err(format!(
+                "row {}, column {}: actor on a {:?} cell", row, column, cell
+            ));
+        }
+
+        let sheet = registry.sheet(&actor.sheet).ok_or_else(|| format!(
+            "row {}, column {}: unknown sprite sheet {}", row, column, actor.sheet
+        ))?;
+
+        if registry.sheets[sheet].animation(&actor.animation).is_none() {
+            return Err(format!(
+                "row {}, column {}: sprite sheet {} has no animation {}",
+                row, column, actor.sheet, actor.animation
+            ));
+        }
+
+        for (x, y) in actor.enemy.iter().flat_map(|enemy| enemy.waypoints.iter()) {
+            let walkable = match cell_at(level, *x, *y) {
+                Some((row, column)) => !level.cells[row][column].is_solid(),
+                None => false
+            };
+
+            if !walkable {
+                return Err(format!(
+                    "row {}, column {}: waypoint ({}, {}) is not on a walkable cell",
+                    row, column, x, y
+                ));
+            }
+        }
+    }
+
+    let (x, y) = level.player_start;
+    let (row, column) = cell_at(level, x, y).ok_or_else(|| format!(
+        "player start ({}, {}) is outside the map", x, y
+    ))?;
+    let cell = level.cells[row][column];
+
+    if !matches!(cell, DungeonCell::Floor(_)) {
+        return Err(format!(
+            "row {}, column {}: player start is on a {:?} cell",
+            row, column, cell
+        ));
+    }
+
+    Ok(())
+}
+
+/// Finds the row and column of the cell containing a point, or `None` when
+/// the point falls outside the map.
+fn cell_at(level: &LevelRon, x: f32, y: f32) -> Option<(usize, usize)> {
+    // Cell centers sit on integer coordinates.
+    let column = (x + 0.5).floor();
+    let row = (y + 0.5).floor();
+
+    if column < 0.0 || row < 0.0
+        || column as usize >= level.width || row as usize >= level.height {
+        return None;
+    }
+
+    Some((row as usize, column as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::Join;
+    use crate::game::model::DungeonCell::{ Wall, Floor, Door };
+
+    /// A corridor two cells long, to break in the tests.
+    const CORRIDOR: &str = r#"(
+        width: 4,
+        height: 3,
+        cells: [
+            [Wall(0), Wall(0), Wall(0), Wall(0)],
+            [Wall(0), Floor(1), Floor(1), Wall(0)],
+            [Wall(0), Wall(0), Wall(0), Wall(0)],
+        ],
+        player_start: (1.0, 1.0),
+        player_angle: 0.0,
+        actors: [],
+    )"#;
+
+    fn registry() -> TextureRegistry {
+        TextureRegistry::from_file("textures/textures.ron").unwrap()
+    }
+
+    /// The error loading a level that should not load.
+    fn error_in(source: &str) -> String {
+        match level_from_str(source, &registry()) {
+            Ok(_) => panic!("loaded {}", source),
+            Err(e) => e
+        }
+    }
+
+    /// The error loading `CORRIDOR` with `from` replaced by `to`.
+    fn error(from: &str, to: &str) -> String {
+        let source = CORRIDOR.replacen(from, to, 1);
+
+        assert_ne!(source, CORRIDOR);
+        error_in(&source)
+    }
+
+    /// `CORRIDOR` with a texture override.
+    fn with_override(texture: &str) -> String {
+        CORRIDOR.replacen(
+            "player_angle: 0.0,", &format!("player_angle: 0.0, textures: [({})],", texture), 1
+        )
+    }
+
+    /// `CORRIDOR` with a sprite.
+    fn with_sprite(sprite: &str) -> String {
+        CORRIDOR.replacen(
+            "player_angle: 0.0,", &format!("player_angle: 0.0, sprites: [({})],", sprite), 1
+        )
+    }
+
+    /// `CORRIDOR` with an actor in its second cell.
+    fn with_actor(actor: &str) -> String {
+        CORRIDOR.replacen(
+            "actors: []", &format!("actors: [(x: 2.0, y: 1.0, angle: 0.0, {})]", actor), 1
+        )
+    }
+
+    #[test]
+    fn loads_the_first_level() {
+        let game = load_level("levels/level00.ron", &registry()).unwrap();
+        let floor = &game.current_map;
+
+        assert_eq!(game.player_position(), Vec3::new(1.0, 8.0, 0.0));
+        assert_eq!((floor.width, floor.height), (10, 10));
+        assert_eq!(floor.cell(0, 0), Some(DungeonCell::Empty));
+        assert_eq!(floor.cell(3, 0), Some(Wall(0)));
+        assert_eq!(floor.cell(6, 4), Some(Door));
+        assert_eq!(floor.cell(1, 8), Some(Floor(1)));
+        assert_eq!(floor.textures.len(), 2);
+        assert_eq!(floor.textures[&(4 + 10)].floor, Some(2));
+        assert_eq!(floor.textures[&(5 + 10)].ceiling, Some(1));
+
+        assert_eq!(game.world.read_storage::<Sprite>().join().count(), 2);
+        assert_eq!(game.world.read_storage::<DirectionalSprite>().join().count(), 2);
+
+        let enemies = game.world.read_storage::<Enemy>();
+        let enemy = enemies.join().next().unwrap();
+        assert_eq!(enemies.join().count(), 1);
+        assert_eq!(enemy.waypoints.len(), 4);
+        assert_eq!(enemy.waypoints[1], Vec3::new(8.0, 5.0, 0.0));
+    }
+
+    #[test]
+    fn loads_a_corridor() {
+        let game = level_from_str(CORRIDOR, &registry()).unwrap();
+
+        assert_eq!(game.player_position(), Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(game.current_map.cells[4..8], [Wall(0), Floor(1), Floor(1), Wall(0)]);
+    }
+
+    #[test]
+    fn rejects_broken_maps() {
+        assert_eq!(error("height: 3", "height: 4"), "expected 4 rows, found 3");
+        assert_eq!(
+            error("[Wall(0), Floor(1), Floor(1), Wall(0)]", "[Wall(0), Floor(1), Wall(0)]"),
+            "row 1: expected 4 columns, found 3"
+        );
+        assert_eq!(
+            error("Floor(1), Wall(0)]", "Floor(9), Wall(0)]"),
+            "row 1, column 2: unknown texture 9"
+        );
+        assert_eq!(
+            error("[Wall(0), Wall(0), Wall(0), Wall(0)]", "[Wall(0), Door, Wall(0), Wall(0)]"),
+            "row 0, column 1: Door cell on the map border"
+        );
+    }
+
+    #[test]
+    fn rejects_odd_texture_overrides() {
+        assert_eq!(
+            error_in(&with_override("x: 4, y: 1")),
+            "row 1, column 4: texture override outside the map"
+        );
+        assert_eq!(
+            error_in(&with_override("x: 0, y: 1")),
+            "row 1, column 0: texture override on a Wall(0) cell"
+        );
+        assert_eq!(
+            error_in(&with_override("x: 1, y: 1, floor: Some(9), ceiling: None")),
+            "row 1, column 1: unknown texture 9"
+        );
+    }
+
+    #[test]
+    fn rejects_odd_sprites() {
+        assert!(level_from_str(&with_sprite("x: 2.0, y: 1.0, texture: 3"), &registry()).is_ok());
+        assert_eq!(
+            error_in(&with_sprite("x: 2.0, y: 1.0, texture: 9")),
+            "row 1, column 2: unknown texture 9"
+        );
+        assert_eq!(
+            error_in(&with_sprite("x: 3.0, y: 1.0, texture: 3")),
+            "row 1, column 3: sprite on a Wall(0) cell"
+        );
+        assert_eq!(
+            error_in(&with_sprite("x: 9.0, y: 1.0, texture: 3")),
+            "sprite (9, 1) is outside the map"
+        );
+    }
+
+    #[test]
+    fn rejects_odd_actors() {
+        let guard = with_actor(r#"sheet: "guard", animation: "stand""#);
+
+        assert!(level_from_str(&guard, &registry()).is_ok());
+        assert_eq!(
+            error_in(&with_actor(r#"sheet: "dog", animation: "stand""#)),
+            "row 1, column 2: unknown sprite sheet dog"
+        );
+        assert_eq!(
+            error_in(&with_actor(r#"sheet: "guard", animation: "dance""#)),
+            "row 1, column 2: sprite sheet guard has no animation dance"
+        );
+        assert_eq!(
+            error_in(&with_actor(
+                r#"sheet: "guard", animation: "stand", enemy: Some((waypoints: [(1.0, 1.0), (1.0, 2.0)]))"#
+            )),
+            "row 1, column 2: waypoint (1, 2) is not on a walkable cell"
+        );
+    }
+
+    #[test]
+    fn rejects_a_player_start_off_the_floor() {
+        assert_eq!(
+            error("player_start: (1.0, 1.0)", "player_start: (7.0, 1.0)"),
+            "player start (7, 1) is outside the map"
+        );
+        assert_eq!(
+            error("player_start: (1.0, 1.0)", "player_start: (0.0, 1.0)"),
+            "row 1, column 0: player start is on a Wall(0) cell"
+        );
+    }
+}
@@ -1,4 +1,20 @@
 //! Game logic and model system.
 
 pub mod model;
+pub mod components;
+pub mod movement;
+pub mod level;
+pub mod collision;
+pub mod raycast;
+pub mod pathfinding;
+pub mod doors;
+pub mod textures;
+pub mod sprites;
+pub mod enemies;
+pub mod weapons;
+pub mod exploration;
+pub mod input;
+pub mod timestep;
+pub mod scheduler;
+pub mod simulation;
 
@@ -1,47 +1,201 @@
 //! Defines the game model.
 
-use slotmap::{ SlotMap, new_key_type };
+use std::collections::HashMap;
+use serde::{ Serialize, Deserialize };
+use specs::{ World, WorldExt, Builder, Entity };
 use glm::{Vec3};
+use crate::game::{
+    collision::PLAYER_RADIUS,
+    components::{ Position, Velocity, Facing, Collider, PlayerControlled, Camera, Sprite,
+        DirectionalSprite, Health, Enemy, Weapon, Player
+    },
+    input::InputState,
+    scheduler::Scheduler,
+    simulation,
+    weapons::Impact
+};
 
+/// Position of a texture in the `TextureRegistry`.
+pub type TextureIndex = u16;
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum DungeonCell {
     Empty,
-    Wall,
-    Floor,
+    Wall(TextureIndex),
+    Floor(TextureIndex),
     Door,
     OpenDoor
 }
 
+impl DungeonCell {
+    /// Solid cells block movement. `Empty` is solid too: it is the void
+    /// outside the map.
+    pub fn is_solid(self) -> bool {
+        match self {
+            DungeonCell::Empty | DungeonCell::Wall(_) | DungeonCell::Door => true,
+            DungeonCell::Floor(_) | DungeonCell::OpenDoor => false
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DungeonFloor {
     pub width: usize,
     pub height: usize,
     pub cells: Vec<DungeonCell>,
+    /// Doors that have been used at least once, by cell index. Doors that
+    /// never moved rest in the state their cell describes.
+    #[serde(skip)]
+    pub doors: HashMap<usize, DoorState>,
+    /// Texture overrides of walkable cells, by cell index.
+    #[serde(default)]
+    pub textures: HashMap<usize, CellTextures>,
+    /// Bumped by `set_cell`, lets the renderer know when to rebuild the
+    /// geometry of the floor.
+    #[serde(skip)]
+    pub revision: u64,
+    /// Bumped whenever a door panel moves, lets the renderer know when to
+    /// rebuild the geometry of the doors.
+    #[serde(skip)]
+    pub door_revision: u64
 }
 
-/// The player will be dismembered in components when the 3d engine is stable
-/// enough
-pub struct Player {
-    pub pos: Vec3,
-    pub angle: f32,
-    pub direction: Vec3,
-    pub speed: Vec3
+/// Replaces the floor or ceiling texture of a cell. A floor override wins
+/// over the texture of a `Floor` cell.
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct CellTextures {
+    pub floor: Option<TextureIndex>,
+    pub ceiling: Option<TextureIndex>
 }
 
-pub trait System {
-    fn update(&self, game: &Game);
+impl DungeonFloor {
+    pub fn new(width: usize, height: usize, cells: Vec<DungeonCell>) -> Self {
+        assert_eq!(cells.len(), width * height);
+
+        Self {
+            width, height, cells,
+            doors: HashMap::new(),
+            textures: HashMap::new(),
+            revision: 0,
+            door_revision: 0
+        }
+    }
+
+    /// Returns the cell at column `x` and row `y`, or `None` when the
+    /// coordinates fall outside the map.
+    pub fn cell(&self, x: i32, y: i32) -> Option<DungeonCell> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return None;
+        }
+
+        Some(self.cells[x as usize + y as usize * self.width])
+    }
+
+    /// Replaces the cell at `index`. Prefer this to writing `cells`
+    /// directly, so that the change gets noticed.
+    pub fn set_cell(&mut self, index: usize, cell: DungeonCell) {
+        if self.cells[index] != cell {
+            self.cells[index] = cell;
+            self.revision += 1;
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum DoorMotion {
+    Closed,
+    Opening,
+    Open,
+    Closing
 }
 
-new_key_type! {
-    pub struct EntityKey;
+#[derive(Copy, Clone, Debug)]
+pub struct DoorState {
+    /// How far the panel slid into the wall: 0 closed, 1 open.
+    pub progress: f32,
+    pub motion: DoorMotion,
+    /// Seconds the door has been standing open.
+    pub open_time: f32
 }
 
-#[derive(Copy, Clone)]
-pub struct Entity {
-    pub key: EntityKey,
-    // Think about ECS when needed.....
+/// A piece of game logic that runs on every tick, see `Scheduler`.
+pub trait System {
+    /// * `game`: the whole game, free to change.
+    /// * `dt`: seconds simulated by the tick.
+    fn update(&mut self, game: &mut Game, dt: f32);
 }
 
+/// Height of the eyes of the player above the floor.
+const EYE_HEIGHT: f32 = 1.77;
+pub const PLAYER_HEALTH: i32 = 100;
+
 pub struct Game {
-    pub player: Player,
+    /// Entities and their components, see `game::components`.
+    pub world: World,
+    /// The entity that the input drives and the camera follows.
+    pub player: Entity,
     pub current_map: DungeonFloor,
-    pub entities: SlotMap<EntityKey, Entity>
+    /// What the player asks for during the current tick.
+    pub input: InputState,
+    /// Systems that run on every tick.
+    pub systems: Scheduler,
+    /// Shots that landed since the frontend last took them, for it to show.
+    pub impacts: Vec<Impact>
+}
+
+impl Game {
+    /// Starts a game on `current_map`.
+    /// * `pos`: where the player stands.
+    /// * `angle`: where the player looks.
+    pub fn new(current_map: DungeonFloor, pos: Vec3, angle: f32) -> Self {
+        let mut world = World::new();
+
+        world.register::<Position>();
+        world.register::<Velocity>();
+        world.register::<Facing>();
+        world.register::<Collider>();
+        world.register::<PlayerControlled>();
+        world.register::<Camera>();
+        world.register::<Sprite>();
+        world.register::<DirectionalSprite>();
+        world.register::<Health>();
+        world.register::<Enemy>();
+        world.register::<Weapon>();
+        world.register::<Player>();
+
+        let player = world.create_entity()
+            .with(Position(pos))
+            .with(Velocity(Vec3::new(0.0, 0.0, 0.0)))
+            .with(Facing::new(angle))
+            .with(Collider { radius: PLAYER_RADIUS })
+            .with(PlayerControlled)
+            .with(Camera { height: EYE_HEIGHT })
+            .with(Health { points: PLAYER_HEALTH })
+            .with(Weapon::pistol())
+            .with(Player::default())
+            .build();
+
+        Self {
+            world, player, current_map,
+            input: InputState::default(),
+            systems: simulation::standard_systems(),
+            impacts: Vec::new()
+        }
+    }
+
+    pub fn player_position(&self) -> Vec3 {
+        self.world.read_storage::<Position>().get(self.player)
+            .expect("the player has no position").0
+    }
+
+    /// Returns: health left to the player, 0 when dead.
+    pub fn player_health(&self) -> i32 {
+        self.world.read_storage::<Health>().get(self.player)
+            .map_or(0, |health| health.points)
+    }
+
+    pub fn player_facing(&self) -> Facing {
+        *self.world.read_storage::<Facing>().get(self.player)
+            .expect("the player has no facing")
+    }
 }
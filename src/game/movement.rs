@@ -0,0 +1,64 @@
+//! Systems that turn the player input into motion, and motion into
+//! positions.
+
+use glm::Vec3;
+use specs::{ Join, WorldExt };
+use crate::game::{
+    collision,
+    components::{ Position, Velocity, Facing, Collider, PlayerControlled },
+    model::{ Game, System }
+};
+
+/// Walking speed, in cells per second.
+pub const WALK_SPEED: f32 = 4.0;
+/// Turning speed, in radians per second.
+pub const TURN_SPEED: f32 = std::f32::consts::PI;
+
+/// Turns the entities tagged `PlayerControlled` and sets their velocity
+/// from the input of the tick.
+pub struct PlayerControlSystem;
+
+impl System for PlayerControlSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        let input = game.input;
+        let controlled = game.world.read_storage::<PlayerControlled>();
+        let mut facings = game.world.write_storage::<Facing>();
+        let mut velocities = game.world.write_storage::<Velocity>();
+
+        for (_, facing, velocity) in (&controlled, &mut facings, &mut velocities).join() {
+            facing.turn(input.turn * TURN_SPEED * dt + input.yaw);
+
+            let right = Vec3::new(facing.direction.y, -facing.direction.x, 0.0);
+            let mut walk = facing.direction * input.forward + right * input.strafe;
+
+            // Walking diagonally is no faster than walking straight.
+            if walk.norm() > 1.0 {
+                walk = walk.normalize();
+            }
+
+            velocity.0 = walk * WALK_SPEED;
+        }
+    }
+}
+
+/// Moves every entity by its velocity. Entities with a `Collider` slide
+/// along the solid cells of the map, the others fly through them.
+pub struct MovementSystem;
+
+impl System for MovementSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        let floor = &game.current_map;
+        let mut positions = game.world.write_storage::<Position>();
+        let velocities = game.world.read_storage::<Velocity>();
+        let colliders = game.world.read_storage::<Collider>();
+
+        for (pos, velocity, collider) in (&mut positions, &velocities, colliders.maybe()).join() {
+            let delta = velocity.0 * dt;
+
+            pos.0 = match collider {
+                Some(collider) => collision::slide(floor, pos.0, delta, collider.radius),
+                None => pos.0 + delta
+            };
+        }
+    }
+}
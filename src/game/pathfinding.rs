@@ -0,0 +1,362 @@
+//! Finds ways through the dungeon grid.
+//!
+//! Agents step from a cell to any of its eight neighbours, diagonally only
+//! when both cells beside the step are passable too, so that nobody cuts the
+//! corner of a wall. `Floor` and `OpenDoor` cells are walkable; closed `Door`
+//! cells let through, at an extra cost, those that can open them, and block
+//! everybody else.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32::consts::SQRT_2;
+use glm::Vec3;
+use crate::game::model::{ DungeonFloor, DungeonCell };
+
+/// Column and row of a cell.
+pub type Cell = (i32, i32);
+
+/// Way planned by `find_path`, kept by whoever follows it.
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub goal: Cell,
+    /// Revision of the floor the route was planned on.
+    pub revision: u64,
+    /// Cells still to go through, the next one last.
+    pub steps: Vec<Cell>
+}
+
+/// Cell containing a point.
+pub fn cell_of(pos: Vec3) -> Cell {
+    // Cell centers sit on integer coordinates.
+    ((pos.x + 0.5).floor() as i32, (pos.y + 0.5).floor() as i32)
+}
+
+pub fn center_of(cell: Cell) -> Vec3 {
+    Vec3::new(cell.0 as f32, cell.1 as f32, 0.0)
+}
+
+/// Finds the shortest way between two cells (A*).
+/// * `door_cost`: extra cost of going through a closed door, in cells, or
+///   `None` when closed doors block.
+/// Returns: the cells to go through after `from`, `to` included, or `None`
+/// when `to` cannot be reached.
+pub fn find_path(floor: &DungeonFloor, from: Cell, to: Cell, door_cost: Option<f32>) -> Option<Vec<Cell>> {
+    let start = index_of(floor, from)?;
+
+    entry_cost(floor, to, door_cost)?;
+
+    let mut costs = vec![f32::INFINITY; floor.cells.len()];
+    let mut came_from = vec![usize::MAX; floor.cells.len()];
+    let mut open = BinaryHeap::new();
+
+    costs[start] = 0.0;
+    open.push(Open { priority: estimate(from, to), cell: from });
+
+    while let Some(Open { priority, cell }) = open.pop() {
+        let index = cell.0 as usize + cell.1 as usize * floor.width;
+
+        if cell == to {
+            let mut path = Vec::new();
+            let mut current = index;
+
+            while current != start {
+                path.push(((current % floor.width) as i32, (current / floor.width) as i32));
+                current = came_from[current];
+            }
+
+            path.reverse();
+            return Some(path);
+        }
+
+        // Reached again at a lower cost since it was queued.
+        if priority > costs[index] + estimate(cell, to) {
+            continue;
+        }
+
+        for_each_neighbour(floor, cell, door_cost, |next, step, entry| {
+            let next_index = next.0 as usize + next.1 as usize * floor.width;
+            let cost = costs[index] + step + entry;
+
+            if cost < costs[next_index] {
+                costs[next_index] = cost;
+                came_from[next_index] = index;
+                open.push(Open { priority: cost + estimate(next, to), cell: next });
+            }
+        });
+    }
+
+    None
+}
+
+/// Cost of the way to a goal from every cell of a floor, laid once and
+/// followed by any number of agents going there (Dijkstra).
+#[derive(Clone, Debug)]
+pub struct FlowField {
+    pub goal: Cell,
+    /// Revision of the floor the field was laid on.
+    pub revision: u64,
+    door_cost: Option<f32>,
+    width: usize,
+    /// By cell index, infinite where the goal cannot be reached from.
+    costs: Vec<f32>
+}
+
+impl FlowField {
+    /// Lays a field leading to `goal`.
+    /// * `door_cost`: see `find_path`.
+    pub fn toward(floor: &DungeonFloor, goal: Cell, door_cost: Option<f32>) -> Self {
+        let mut costs = vec![f32::INFINITY; floor.cells.len()];
+        let mut open = BinaryHeap::new();
+
+        if let Some(index) = index_of(floor, goal) {
+            costs[index] = 0.0;
+            open.push(Open { priority: 0.0, cell: goal });
+        }
+
+        while let Some(Open { priority, cell }) = open.pop() {
+            let index = cell.0 as usize + cell.1 as usize * floor.width;
+
+            if priority > costs[index] {
+                continue;
+            }
+
+            // Whoever steps from `next` into `cell` pays to enter `cell`.
+            let entry = entry_cost(floor, cell, door_cost).unwrap_or(0.0);
+
+            for_each_neighbour(floor, cell, door_cost, |next, step, _| {
+                let next_index = next.0 as usize + next.1 as usize * floor.width;
+                let cost = priority + step + entry;
+
+                if cost < costs[next_index] {
+                    costs[next_index] = cost;
+                    open.push(Open { priority: cost, cell: next });
+                }
+            });
+        }
+
+        Self {
+            goal,
+            revision: floor.revision,
+            door_cost,
+            width: floor.width,
+            costs
+        }
+    }
+
+    /// Tells whether the field leads to `goal` on the floor as it is now.
+    pub fn leads_to(&self, floor: &DungeonFloor, goal: Cell) -> bool {
+        self.goal == goal && self.revision == floor.revision
+    }
+
+    /// Cost of the way from `cell` to the goal, or `None` when there is no
+    /// way.
+    pub fn cost(&self, cell: Cell) -> Option<f32> {
+        if cell.0 < 0 || cell.1 < 0 || cell.0 as usize >= self.width {
+            return None;
+        }
+
+        match self.costs.get(cell.0 as usize + cell.1 as usize * self.width) {
+            Some(cost) if cost.is_finite() => Some(*cost),
+            _ => None
+        }
+    }
+
+    /// Picks the neighbour of `cell` to step into on the way to the goal.
+    /// * `floor`: the floor the field was laid on.
+    /// Returns: `None` at the goal, or where there is no way.
+    pub fn next_step(&self, floor: &DungeonFloor, cell: Cell) -> Option<Cell> {
+        if cell == self.goal {
+            return None;
+        }
+
+        let mut best: Option<(f32, Cell)> = None;
+
+        for_each_neighbour(floor, cell, self.door_cost, |next, step, entry| {
+            if let Some(cost) = self.cost(next) {
+                let cost = cost + step + entry;
+
+                if best.map(|(lowest, _)| cost < lowest).unwrap_or(true) {
+                    best = Some((cost, next));
+                }
+            }
+        });
+
+        best.map(|(_, next)| next)
+    }
+}
+
+/// Cell waiting in the open set of a search, the lowest `priority` first.
+#[derive(Copy, Clone, PartialEq)]
+struct Open {
+    priority: f32,
+    cell: Cell
+}
+
+impl Eq for Open { }
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `BinaryHeap` pops the greatest.
+        other.priority.partial_cmp(&self.priority).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Calls `visit` with every neighbour an agent can step into from `cell`,
+/// the length of the step and the extra cost of entering the neighbour.
+fn for_each_neighbour<F>(floor: &DungeonFloor, cell: Cell, door_cost: Option<f32>, mut visit: F)
+    where F: FnMut(Cell, f32, f32) {
+    let (x, y) = cell;
+
+    for &(dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)].iter() {
+        let next = (x + dx, y + dy);
+        let entry = match entry_cost(floor, next, door_cost) {
+            Some(cost) => cost,
+            None => continue
+        };
+
+        if dx != 0 && dy != 0 {
+            let beside = entry_cost(floor, (x + dx, y), door_cost).is_some()
+                && entry_cost(floor, (x, y + dy), door_cost).is_some();
+
+            if beside {
+                visit(next, SQRT_2, entry);
+            }
+        } else {
+            visit(next, 1.0, entry);
+        }
+    }
+}
+
+/// Extra cost of entering a cell, or `None` when the cell cannot be entered.
+fn entry_cost(floor: &DungeonFloor, cell: Cell, door_cost: Option<f32>) -> Option<f32> {
+    match floor.cell(cell.0, cell.1)? {
+        DungeonCell::Floor(_) | DungeonCell::OpenDoor => Some(0.0),
+        DungeonCell::Door => door_cost,
+        DungeonCell::Empty | DungeonCell::Wall(_) => None
+    }
+}
+
+fn index_of(floor: &DungeonFloor, cell: Cell) -> Option<usize> {
+    floor.cell(cell.0, cell.1)?;
+
+    Some(cell.0 as usize + cell.1 as usize * floor.width)
+}
+
+/// Length of the shortest way between two cells on an open floor, never
+/// more than the real way.
+fn estimate(from: Cell, to: Cell) -> f32 {
+    let dx = (to.0 - from.0).abs() as f32;
+    let dy = (to.1 - from.1).abs() as f32;
+
+    dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::model::DungeonCell::{ Door, OpenDoor };
+    use crate::uglythings::{ build_experimental_game, generated_map };
+
+    /// Length of a path walked from `from`.
+    fn length(from: Cell, path: &[Cell]) -> f32 {
+        let mut previous = from;
+
+        path.iter()
+            .map(|&cell| {
+                let step = if cell.0 != previous.0 && cell.1 != previous.1 { SQRT_2 } else { 1.0 };
+                previous = cell;
+                step
+            })
+            .sum()
+    }
+
+    /// Closes the way into the east room of the test map with doors.
+    fn east_room_doors(floor: &mut DungeonFloor) {
+        floor.set_cell(6 + 4 * floor.width, Door);
+        floor.set_cell(6 + 5 * floor.width, Door);
+    }
+
+    #[test]
+    fn goes_around_the_pillar() {
+        let game = build_experimental_game();
+        let path = find_path(&game.current_map, (2, 5), (4, 5), None).unwrap();
+
+        // Corners are not cut: two steps up, two steps down.
+        assert_eq!(path.len(), 4);
+        assert_eq!(path.last(), Some(&(4, 5)));
+        assert!(!path.contains(&(3, 5)));
+
+        let mut previous = (2, 5);
+
+        for &cell in path.iter() {
+            assert!((cell.0 - previous.0).abs() <= 1 && (cell.1 - previous.1).abs() <= 1);
+            assert!(!game.current_map.cell(cell.0, cell.1).unwrap().is_solid());
+            previous = cell;
+        }
+
+        assert_eq!(find_path(&game.current_map, (2, 5), (8, 8), None), None);
+        assert_eq!(find_path(&game.current_map, (2, 5), (2, 5), None), Some(Vec::new()));
+    }
+
+    #[test]
+    fn goes_through_doors_at_a_cost() {
+        let mut game = build_experimental_game();
+        let floor = &mut game.current_map;
+
+        east_room_doors(floor);
+        assert_eq!(find_path(floor, (1, 8), (8, 4), None), None);
+
+        let path = find_path(floor, (1, 8), (8, 4), Some(5.0)).unwrap();
+        assert!(path.contains(&(6, 4)) || path.contains(&(6, 5)));
+
+        floor.set_cell(6 + 5 * floor.width, OpenDoor);
+        let path = find_path(floor, (1, 8), (8, 4), None).unwrap();
+        assert!(path.contains(&(6, 5)));
+    }
+
+    #[test]
+    fn flow_field_agrees_with_a_star() {
+        let mut game = build_experimental_game();
+        let floor = &mut game.current_map;
+
+        east_room_doors(floor);
+
+        let field = FlowField::toward(floor, (1, 8), Some(5.0));
+
+        for &from in [(8, 4), (4, 1), (5, 5), (1, 3)].iter() {
+            let path = find_path(floor, from, (1, 8), Some(5.0)).unwrap();
+            let mut cell = from;
+            let mut steps = Vec::new();
+
+            while let Some(next) = field.next_step(floor, cell) {
+                steps.push(next);
+                cell = next;
+                assert!(steps.len() < 100);
+            }
+
+            assert_eq!(cell, (1, 8));
+            assert!((length(from, &steps) - length(from, &path)).abs() < 1e-4);
+            assert!(field.cost(from).unwrap() >= length(from, &path));
+        }
+
+        assert_eq!(field.cost((8, 8)), None);
+        assert!(field.leads_to(floor, (1, 8)));
+        floor.set_cell(6 + 5 * floor.width, OpenDoor);
+        assert!(!field.leads_to(floor, (1, 8)));
+    }
+
+    #[test]
+    fn flow_field_agrees_with_a_star_across_a_large_map() {
+        let floor = generated_map(256);
+        let path = find_path(&floor, (1, 1), (254, 254), Some(5.0)).unwrap();
+        let field = FlowField::toward(&floor, (254, 254), Some(5.0));
+
+        assert!((field.cost((1, 1)).unwrap() - length((1, 1), &path)).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,153 @@
+//! Casts rays through the dungeon grid, one cell at a time.
+
+use glm::Vec3;
+use crate::game::model::DungeonFloor;
+
+/// Where a ray met a solid cell.
+#[derive(Copy, Clone, Debug)]
+pub struct RayHit {
+    pub point: Vec3,
+    /// From the origin of the ray, in cells.
+    pub distance: f32,
+    /// Column of the cell hit.
+    pub x: i32,
+    /// Row of the cell hit.
+    pub y: i32
+}
+
+/// Follows a ray on the floor plane until it enters a solid cell, stepping
+/// from cell border to cell border (DDA). Cells outside the map count as
+/// solid.
+/// * `origin`: start of the ray; `z` is ignored.
+/// * `direction`: direction of the ray; `z` is ignored.
+/// * `max_distance`: how far the ray goes, in cells.
+/// Returns: the first solid cell hit, if any within `max_distance`.
+pub fn cast(floor: &DungeonFloor, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RayHit> {
+    cast_through(floor, origin, direction, max_distance, |_, _| ())
+}
+
+/// Same as `cast`, also telling every cell the ray goes through.
+/// * `visit`: called with the column and row of each cell the ray enters,
+///   in order, from the one of the origin to the solid one hit.
+pub fn cast_through<F: FnMut(i32, i32)>(
+    floor: &DungeonFloor, origin: Vec3, direction: Vec3, max_distance: f32, mut visit: F
+) -> Option<RayHit> {
+    let flat = Vec3::new(direction.x, direction.y, 0.0);
+
+    if flat.norm() < 1e-6 {
+        return None;
+    }
+
+    let direction = flat.normalize();
+    let hit = |distance: f32, x: i32, y: i32| RayHit {
+        point: origin + direction * distance,
+        distance, x, y
+    };
+    // Cells span `[x - 0.5, x + 0.5]`, shift them to `[x, x + 1]`.
+    let start_x = origin.x + 0.5;
+    let start_y = origin.y + 0.5;
+    let mut x = start_x.floor() as i32;
+    let mut y = start_y.floor() as i32;
+
+    visit(x, y);
+
+    if is_solid(floor, x, y) {
+        return Some(hit(0.0, x, y));
+    }
+
+    let (step_x, delta_x, mut next_x) = axis_steps(start_x, direction.x);
+    let (step_y, delta_y, mut next_y) = axis_steps(start_y, direction.y);
+
+    loop {
+        let distance;
+
+        if next_x < next_y {
+            distance = next_x;
+            next_x += delta_x;
+            x += step_x;
+        } else {
+            distance = next_y;
+            next_y += delta_y;
+            y += step_y;
+        }
+
+        if distance > max_distance {
+            return None;
+        }
+
+        visit(x, y);
+
+        if is_solid(floor, x, y) {
+            return Some(hit(distance, x, y));
+        }
+    }
+}
+
+/// Tells whether nothing solid stands between two points.
+pub fn line_of_sight(floor: &DungeonFloor, from: Vec3, to: Vec3) -> bool {
+    let delta = Vec3::new(to.x - from.x, to.y - from.y, 0.0);
+    let distance = delta.norm();
+
+    distance < 1e-6 || cast(floor, from, delta, distance).is_none()
+}
+
+/// Works out how a ray crosses the cell borders along one axis.
+/// * `start`: shifted coordinate of the origin on the axis.
+/// * `direction`: component of the unit direction on the axis.
+/// Returns: step between cells, distance between borders and distance to
+/// the first border.
+fn axis_steps(start: f32, direction: f32) -> (i32, f32, f32) {
+    if direction > 0.0 {
+        let delta = 1.0 / direction;
+
+        (1, delta, (start.floor() + 1.0 - start) * delta)
+    } else if direction < 0.0 {
+        let delta = -1.0 / direction;
+
+        (-1, delta, (start - start.floor()) * delta)
+    } else {
+        (0, f32::INFINITY, f32::INFINITY)
+    }
+}
+
+fn is_solid(floor: &DungeonFloor, x: i32, y: i32) -> bool {
+    match floor.cell(x, y) {
+        Some(cell) => cell.is_solid(),
+        None => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uglythings::build_experimental_game;
+
+    #[test]
+    fn stops_at_the_first_wall() {
+        let game = build_experimental_game();
+        let hit = cast(
+            &game.current_map, Vec3::new(1.0, 8.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 100.0
+        ).unwrap();
+
+        assert_eq!((hit.x, hit.y), (6, 8));
+        assert!((hit.distance - 4.5).abs() < 1e-4);
+        assert!((hit.point.x - 5.5).abs() < 1e-4);
+
+        let hit = cast(
+            &game.current_map, Vec3::new(1.0, 8.0, 0.0), Vec3::new(1.0, -1.0, 0.0), 100.0
+        ).unwrap();
+
+        // Diagonally, through the corners of the cells, into the pillar.
+        assert_eq!((hit.x, hit.y), (3, 5));
+    }
+
+    #[test]
+    fn pillars_block_the_sight() {
+        let game = build_experimental_game();
+        let floor = &game.current_map;
+
+        assert!(!line_of_sight(floor, Vec3::new(2.0, 5.0, 0.0), Vec3::new(4.0, 5.0, 0.0)));
+        assert!(line_of_sight(floor, Vec3::new(2.0, 6.0, 0.0), Vec3::new(4.0, 6.0, 0.0)));
+        assert!(cast(floor, Vec3::new(2.0, 6.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 2.0).is_none());
+    }
+}
@@ -0,0 +1,64 @@
+//! Runs the systems of the game in a fixed order.
+
+use crate::game::model::{ Game, System };
+
+/// Ordered list of systems. Each tick runs them all, in the order they were
+/// added, so a system sees what the previous ones did during the same tick.
+#[derive(Default)]
+pub struct Scheduler {
+    systems: Vec<Box<dyn System>>
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a system, to run after those already added.
+    pub fn with<S: System + 'static>(mut self, system: S) -> Self {
+        self.systems.push(Box::new(system));
+        self
+    }
+
+    /// Runs every system once.
+    /// * `dt`: seconds simulated by the tick.
+    pub fn run(&mut self, game: &mut Game, dt: f32) {
+        for system in self.systems.iter_mut() {
+            system.update(game, dt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ cell::RefCell, rc::Rc };
+    use crate::uglythings::build_experimental_game;
+
+    struct Record {
+        name: &'static str,
+        log: Rc<RefCell<Vec<(&'static str, f32)>>>
+    }
+
+    impl System for Record {
+        fn update(&mut self, _game: &mut Game, dt: f32) {
+            self.log.borrow_mut().push((self.name, dt));
+        }
+    }
+
+    #[test]
+    fn runs_systems_in_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler = Scheduler::new()
+            .with(Record { name: "first", log: log.clone() })
+            .with(Record { name: "second", log: log.clone() });
+        let mut game = build_experimental_game();
+
+        scheduler.run(&mut game, 0.5);
+        scheduler.run(&mut game, 0.25);
+
+        assert_eq!(*log.borrow(), vec![
+            ("first", 0.5), ("second", 0.5), ("first", 0.25), ("second", 0.25)
+        ]);
+    }
+}
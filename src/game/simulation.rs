@@ -0,0 +1,116 @@
+//! Advances the game by fixed ticks.
+
+use specs::WorldExt;
+use crate::game::{
+    doors::DoorSystem,
+    enemies::EnemyAiSystem,
+    exploration::ExplorationSystem,
+    input::InputState,
+    model::Game,
+    movement::{ PlayerControlSystem, MovementSystem },
+    scheduler::Scheduler,
+    sprites::SpriteAnimationSystem,
+    weapons::WeaponSystem
+};
+
+/// The systems every game runs, in order.
+pub fn standard_systems() -> Scheduler {
+    Scheduler::new()
+        .with(PlayerControlSystem)
+        .with(WeaponSystem)
+        .with(EnemyAiSystem::default())
+        .with(MovementSystem)
+        .with(DoorSystem)
+        .with(ExplorationSystem::default())
+        .with(SpriteAnimationSystem)
+}
+
+impl Game {
+    /// Runs the simulation for a tick.
+    /// * `dt`: seconds simulated by the tick.
+    /// * `input`: what the player asks for during the tick.
+    pub fn tick(&mut self, dt: f32, input: &InputState) {
+        self.input = *input;
+
+        // Systems get the whole game, so they step out of it while running.
+        let mut systems = std::mem::take(&mut self.systems);
+        systems.run(self, dt);
+        self.systems = systems;
+
+        self.world.maintain();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glm::Vec3;
+    use crate::game::components::Position;
+    use crate::game::movement::{ WALK_SPEED, TURN_SPEED };
+    use crate::uglythings::build_experimental_game;
+
+    fn set_player_position(game: &mut Game, pos: Vec3) {
+        game.world.write_storage::<Position>().insert(game.player, Position(pos)).unwrap();
+    }
+
+    const DT: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn walks_at_a_steady_speed() {
+        let mut game = build_experimental_game();
+        let input = InputState { forward: 1.0, ..Default::default() };
+
+        for _ in 0..30 {
+            game.tick(DT, &input);
+        }
+
+        let pos = game.player_position();
+        assert!((pos.x - (1.0 + WALK_SPEED * 0.5)).abs() < 1e-3);
+        assert!((pos.y - 8.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn turns_and_walks_together() {
+        let mut game = build_experimental_game();
+        let input = InputState { forward: 1.0, turn: 1.0, ..Default::default() };
+
+        for _ in 0..30 {
+            game.tick(DT, &input);
+        }
+
+        let pos = game.player_position();
+        assert!((game.player_facing().angle - TURN_SPEED * 0.5).abs() < 1e-3);
+        // Swept a quarter turn from +x towards +y, the second half of it
+        // sliding along the wall on row 9.
+        assert!(pos.x > 1.5);
+        assert!(pos.y > 8.0);
+    }
+
+    #[test]
+    fn adds_mouse_yaw_to_turning() {
+        let mut game = build_experimental_game();
+        let input = InputState { turn: 1.0, yaw: 0.25, ..Default::default() };
+
+        game.tick(DT, &input);
+
+        let facing = game.player_facing();
+        assert!((facing.angle - (TURN_SPEED * DT + 0.25)).abs() < 1e-6);
+        assert!((facing.direction.y - facing.angle.sin()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn strafes_sideways() {
+        let mut game = build_experimental_game();
+        set_player_position(&mut game, Vec3::new(4.0, 7.0, 0.0));
+        let input = InputState { strafe: 1.0, ..Default::default() };
+
+        for _ in 0..15 {
+            game.tick(DT, &input);
+        }
+
+        // Facing east, right is towards negative y.
+        let pos = game.player_position();
+        assert!((pos.y - (7.0 - WALK_SPEED * 0.25)).abs() < 1e-3);
+        assert!((pos.x - 4.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,183 @@
+//! Sprite sheets of things that look different from every side.
+//!
+//! A sheet is an image cut in frames the size of a texture: one column per
+//! rotation, `ROTATIONS` of them, and one row per animation frame. Column
+//! `k` shows the thing as seen from `k` eighths of a turn counterclockwise
+//! from its front, so column 0 is the front, 2 its left side, 4 its back
+//! and 6 its right side.
+
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::path::Path;
+use serde::Deserialize;
+use specs::{ Join, WorldExt };
+use glm::Vec3;
+use crate::game::{
+    components::DirectionalSprite,
+    model::{ Game, System, TextureIndex }
+};
+
+/// Columns of a sprite sheet.
+pub const ROTATIONS: usize = 8;
+
+/// On-disk layout of a sprite sheet.
+#[derive(Deserialize)]
+struct SpriteSheetRon {
+    image: String,
+    rows: usize,
+    animations: BTreeMap<String, AnimationRon>
+}
+
+#[derive(Deserialize)]
+struct AnimationRon {
+    /// Sheet rows the animation goes through, in order.
+    rows: Vec<usize>,
+    /// Frames per second.
+    fps: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub name: String,
+    pub rows: Vec<usize>,
+    pub fps: f32
+}
+
+#[derive(Clone, Debug)]
+pub struct SpriteSheet {
+    pub name: String,
+    pub image: String,
+    pub rows: usize,
+    /// Texture array layer of the frame in the first row and column.
+    pub first_layer: TextureIndex,
+    pub animations: Vec<Animation>
+}
+
+impl SpriteSheet {
+    /// Loads a sheet from a RON file.
+    /// * `first_layer`: texture array layer the sheet starts from.
+    /// Returns: `Result<SpriteSheet, String>`.
+    pub fn from_file<P: AsRef<Path>>(
+        name: &str, path: P, first_layer: TextureIndex
+    ) -> Result<Self, String> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.as_ref().display(), error));
+            }
+        };
+        let sheet: SpriteSheetRon = ron::de::from_str(&source)
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+
+        for (name, animation) in sheet.animations.iter() {
+            if animation.rows.is_empty() {
+                return Err(format!(
+                    "{}: animation {} has no rows", path.as_ref().display(), name
+                ));
+            }
+
+            if let Some(row) = animation.rows.iter().find(|row| **row >= sheet.rows) {
+                return Err(format!(
+                    "{}: animation {} uses row {} of {}",
+                    path.as_ref().display(), name, row, sheet.rows
+                ));
+            }
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            image: sheet.image,
+            rows: sheet.rows,
+            first_layer,
+            animations: sheet.animations.into_iter()
+                .map(|(name, animation)| Animation {
+                    name,
+                    rows: animation.rows,
+                    fps: animation.fps
+                })
+                .collect()
+        })
+    }
+
+    /// Texture array layers the sheet takes.
+    pub fn layers(&self) -> usize {
+        self.rows * ROTATIONS
+    }
+
+    pub fn animation(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|animation| animation.name == name)
+    }
+
+    /// Layer of the first column of the frame an animation shows at `time`.
+    /// Animations loop.
+    /// * `animation`: index in `animations`.
+    /// * `time`: seconds since the animation started.
+    pub fn frame_layer(&self, animation: usize, time: f32) -> TextureIndex {
+        let animation = &self.animations[animation];
+        let frame = (time * animation.fps) as usize % animation.rows.len();
+
+        self.first_layer + (animation.rows[frame] * ROTATIONS) as TextureIndex
+    }
+}
+
+/// Picks the sheet column that shows a thing facing `facing` to a camera
+/// looking along `view_facing`.
+pub fn rotation_column(facing: Vec3, view_facing: Vec3) -> usize {
+    // The camera sees the thing from the side opposite to where it looks.
+    let seen_from = (-view_facing.y).atan2(-view_facing.x) - facing.y.atan2(facing.x);
+    let step = 2.0 * PI / ROTATIONS as f32;
+    let column = (seen_from / step).round() as i32;
+
+    column.rem_euclid(ROTATIONS as i32) as usize
+}
+
+/// Plays the animations of directional sprites.
+pub struct SpriteAnimationSystem;
+
+impl System for SpriteAnimationSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        for sprite in (&mut game.world.write_storage::<DirectionalSprite>()).join() {
+            sprite.time += dt;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_side_facing_the_camera() {
+        let east = Vec3::new(1.0, 0.0, 0.0);
+        let north = Vec3::new(0.0, 1.0, 0.0);
+
+        // Looking west at something facing east: its front.
+        assert_eq!(rotation_column(east, -east), 0);
+        // Looking east at its back.
+        assert_eq!(rotation_column(east, east), 4);
+        // Looking south, from the north: its left side.
+        assert_eq!(rotation_column(east, -north), 2);
+        assert_eq!(rotation_column(east, north), 6);
+        assert_eq!(rotation_column(east, Vec3::new(-1.0, -1.0, 0.0).normalize()), 1);
+    }
+
+    #[test]
+    fn loops_animation_rows() {
+        let sheet = SpriteSheet {
+            name: "guard".to_string(),
+            image: String::new(),
+            rows: 3,
+            first_layer: 10,
+            animations: vec![Animation {
+                name: "walk".to_string(),
+                rows: vec![1, 2],
+                fps: 4.0
+            }]
+        };
+
+        assert_eq!(sheet.animation("walk"), Some(0));
+        assert_eq!(sheet.frame_layer(0, 0.1), 10 + 8);
+        assert_eq!(sheet.frame_layer(0, 0.3), 10 + 16);
+        assert_eq!(sheet.frame_layer(0, 0.6), 10 + 8);
+    }
+}
@@ -0,0 +1,135 @@
+//! Registry of the textures levels refer to by index.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::Deserialize;
+use crate::game::{
+    model::TextureIndex,
+    sprites::SpriteSheet,
+    weapons::ViewModel
+};
+
+/// On-disk layout of the texture manifest.
+#[derive(Deserialize)]
+struct TextureManifestRon {
+    textures: Vec<String>,
+    floor: TextureIndex,
+    ceiling: TextureIndex,
+    door: TextureIndex,
+    #[serde(default)]
+    impact: Option<TextureIndex>,
+    /// Sprite sheet files by name.
+    #[serde(default)]
+    sheets: BTreeMap<String, String>,
+    /// View model file of the weapon of the player.
+    #[serde(default)]
+    view_model: Option<String>
+}
+
+/// Ordered list of texture image paths. Cells name textures by their
+/// position in `textures`.
+#[derive(Clone, Debug)]
+pub struct TextureRegistry {
+    pub textures: Vec<String>,
+    /// Floor of walkable cells that are not `Floor`, like doors.
+    pub floor: TextureIndex,
+    /// Ceiling of cells without a ceiling override.
+    pub ceiling: TextureIndex,
+    /// Door panels.
+    pub door: TextureIndex,
+    /// Puff shown where shots land, if any.
+    pub impact: Option<TextureIndex>,
+    /// Sprite sheets, whose frames follow `textures` in the texture array.
+    pub sheets: Vec<SpriteSheet>,
+    /// Weapon of the player, whose frames follow the sheets.
+    pub view_model: Option<ViewModel>
+}
+
+impl TextureRegistry {
+    /// Loads the registry from a RON manifest.
+    /// * `path`: manifest file, usually `textures/textures.ron`.
+    /// Returns: `Result<TextureRegistry, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.as_ref().display(), error));
+            }
+        };
+        let manifest: TextureManifestRon = ron::de::from_str(&source)
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        let mut sheets = Vec::new();
+        let mut layer = manifest.textures.len();
+
+        for (name, sheet_path) in manifest.sheets.iter() {
+            let sheet = SpriteSheet::from_file(name, sheet_path, layer as TextureIndex)?;
+            layer += sheet.layers();
+            sheets.push(sheet);
+        }
+
+        let view_model = match &manifest.view_model {
+            Some(model_path) => {
+                let model = ViewModel::from_file(model_path, layer as TextureIndex)?;
+                layer += model.frames;
+                Some(model)
+            }
+            None => None
+        };
+
+        if layer > TextureIndex::MAX as usize {
+            return Err(format!(
+                "{}: {} texture layers do not fit in a texture index",
+                path.as_ref().display(), layer
+            ));
+        }
+
+        let registry = Self {
+            textures: manifest.textures,
+            floor: manifest.floor,
+            ceiling: manifest.ceiling,
+            door: manifest.door,
+            impact: manifest.impact,
+            sheets,
+            view_model
+        };
+
+        for (name, index) in [
+            ("floor", registry.floor),
+            ("ceiling", registry.ceiling),
+            ("door", registry.door)
+        ].iter().chain(registry.impact.map(|index| ("impact", index)).iter()) {
+            if !registry.contains(*index) {
+                return Err(format!(
+                    "{}: {} texture {} out of range", path.as_ref().display(), name, index
+                ));
+            }
+        }
+
+        Ok(registry)
+    }
+
+    pub fn contains(&self, index: TextureIndex) -> bool {
+        (index as usize) < self.textures.len()
+    }
+
+    pub fn sheet(&self, name: &str) -> Option<usize> {
+        self.sheets.iter().position(|sheet| sheet.name == name)
+    }
+
+    /// Image files to load in the texture array, in layer order. Sheets
+    /// and view models hold many layers each.
+    pub fn images(&self) -> Vec<&str> {
+        self.textures.iter()
+            .chain(self.sheets.iter().map(|sheet| &sheet.image))
+            .chain(self.view_model.iter().map(|model| &model.image))
+            .map(|image| image.as_str())
+            .collect()
+    }
+
+    /// Layers of the texture array.
+    pub fn layer_count(&self) -> usize {
+        self.textures.len()
+            + self.sheets.iter().map(|sheet| sheet.layers()).sum::<usize>()
+            + self.view_model.as_ref().map(|model| model.frames).unwrap_or(0)
+    }
+}
@@ -0,0 +1,63 @@
+//! Fixed timestep bookkeeping, to run the simulation at a steady rate no
+//! matter how fast frames are rendered.
+
+/// Longest frame the accumulator takes in. A longer stall, like dragging
+/// the window, would otherwise be followed by a burst of catch-up ticks.
+const MAX_FRAME_TIME: f32 = 0.25;
+
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32
+}
+
+impl FixedTimestep {
+    /// * `rate`: ticks per second.
+    pub fn new(rate: f32) -> Self {
+        Self { step: 1.0 / rate, accumulator: 0.0 }
+    }
+
+    /// Seconds simulated by each tick.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Accounts for a rendered frame.
+    /// * `frame_time`: seconds since the previous frame.
+    /// Returns: how many ticks to run before rendering.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.min(MAX_FRAME_TIME);
+
+        let ticks = (self.accumulator / self.step).floor();
+        self.accumulator -= ticks * self.step;
+
+        ticks as u32
+    }
+
+    /// How far the render time got into the next tick, from 0 to 1. Use it
+    /// to blend the state before and after the last tick.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulates_partial_ticks() {
+        let mut timestep = FixedTimestep::new(60.0);
+
+        assert_eq!(timestep.advance(1.0 / 120.0), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(1.0 / 120.0 + 1e-4), 1);
+        assert_eq!(timestep.advance(1.0 / 20.0), 3);
+    }
+
+    #[test]
+    fn clamps_long_frames() {
+        let mut timestep = FixedTimestep::new(64.0);
+
+        assert_eq!(timestep.advance(10.0), 16);
+    }
+}
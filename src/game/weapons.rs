@@ -0,0 +1,325 @@
+//! Hitscan weapons: shots land the instant they are fired, on the first wall
+//! or collider along the line of fire.
+
+use std::path::Path;
+use serde::Deserialize;
+use glm::Vec3;
+use specs::{ Entity, Join, WorldExt };
+use crate::game::{
+    components::{ Position, Facing, Collider, Weapon },
+    enemies,
+    model::{ Game, System, TextureIndex },
+    raycast::cast
+};
+
+pub const PISTOL_DAMAGE: i32 = 10;
+/// How far a pistol shot carries, in cells.
+pub const PISTOL_RANGE: f32 = 64.0;
+/// Seconds between pistol shots.
+pub const PISTOL_COOLDOWN: f32 = 0.4;
+/// Rounds the pistol comes with.
+pub const PISTOL_AMMO: i32 = 50;
+
+impl Weapon {
+    pub fn pistol() -> Self {
+        Self {
+            damage: PISTOL_DAMAGE,
+            range: PISTOL_RANGE,
+            cooldown: PISTOL_COOLDOWN,
+            reload: 0.0,
+            ammo: Some(PISTOL_AMMO)
+        }
+    }
+}
+
+/// Share of the cooldown the fire frames of a view model take, the reload
+/// frames take the rest.
+pub const FIRE_SHARE: f32 = 0.5;
+
+/// On-disk layout of a view model.
+#[derive(Deserialize)]
+struct ViewModelRon {
+    image: String,
+    frames: usize,
+    /// Frames shown after a shot, in order.
+    fire: Vec<usize>,
+    /// Frames shown while the weapon gets ready again, in order.
+    reload: Vec<usize>
+}
+
+/// The weapon as the player holds it: frames the size of a texture, side by
+/// side, the first one showing the weapon ready to fire.
+#[derive(Clone, Debug)]
+pub struct ViewModel {
+    pub image: String,
+    pub frames: usize,
+    /// Texture array layer of the first frame.
+    pub first_layer: TextureIndex,
+    pub fire: Vec<usize>,
+    pub reload: Vec<usize>
+}
+
+impl ViewModel {
+    /// Loads a view model from a RON file.
+    /// * `first_layer`: texture array layer the frames start from.
+    /// Returns: `Result<ViewModel, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P, first_layer: TextureIndex) -> Result<Self, String> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.as_ref().display(), error));
+            }
+        };
+        let model: ViewModelRon = ron::de::from_str(&source)
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+
+        if model.frames == 0 {
+            return Err(format!("{}: no frames", path.as_ref().display()));
+        }
+
+        if let Some(frame) = model.fire.iter().chain(model.reload.iter())
+            .find(|frame| **frame >= model.frames) {
+            return Err(format!(
+                "{}: uses frame {} of {}", path.as_ref().display(), frame, model.frames
+            ));
+        }
+
+        Ok(Self {
+            image: model.image,
+            frames: model.frames,
+            first_layer,
+            fire: model.fire,
+            reload: model.reload
+        })
+    }
+
+    /// Layer of the frame that shows a weapon: ready to fire, or going
+    /// through the fire frames then the reload frames while it cools down.
+    pub fn frame_layer(&self, weapon: &Weapon) -> TextureIndex {
+        let frame = if weapon.reload <= 0.0 || weapon.cooldown <= 0.0 {
+            0
+        } else {
+            let progress = 1.0 - weapon.reload / weapon.cooldown;
+            let (frames, time) = if progress < FIRE_SHARE {
+                (&self.fire, progress / FIRE_SHARE)
+            } else {
+                (&self.reload, (progress - FIRE_SHARE) / (1.0 - FIRE_SHARE))
+            };
+
+            match frames.len() {
+                0 => 0,
+                count => frames[((time * count as f32) as usize).min(count - 1)]
+            }
+        };
+
+        self.first_layer + frame as TextureIndex
+    }
+}
+
+/// Where a shot landed.
+#[derive(Copy, Clone, Debug)]
+pub struct Impact {
+    pub point: Vec3,
+    /// Entity hit, `None` when the shot hit a wall.
+    pub entity: Option<Entity>
+}
+
+/// Fires a shot from where an entity stands, along where it looks, and
+/// hurts whatever it hits. The impact is also queued in `Game::impacts`.
+/// * `shooter`: entity firing, never hit by its own shot.
+/// * `range`: how far the shot carries, in cells.
+/// Returns: where the shot landed, `None` when it hit nothing in range.
+pub fn fire(game: &mut Game, shooter: Entity, damage: i32, range: f32) -> Option<Impact> {
+    let (origin, direction) = {
+        let positions = game.world.read_storage::<Position>();
+        let facings = game.world.read_storage::<Facing>();
+
+        match (positions.get(shooter), facings.get(shooter)) {
+            (Some(pos), Some(facing)) => (pos.0, facing.direction),
+            _ => return None
+        }
+    };
+    let wall = cast(&game.current_map, origin, direction, range);
+    let reach = wall.map(|hit| hit.distance).unwrap_or(range);
+    let target = {
+        let entities = game.world.entities();
+        let positions = game.world.read_storage::<Position>();
+        let colliders = game.world.read_storage::<Collider>();
+
+        (&entities, &positions, &colliders).join()
+            .filter(|(entity, _, _)| *entity != shooter)
+            .filter_map(|(entity, pos, collider)| {
+                ray_circle(origin, direction, pos.0, collider.radius).map(|t| (entity, t))
+            })
+            .filter(|(_, distance)| *distance <= reach)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    };
+
+    let impact = match (target, wall) {
+        (Some((entity, distance)), _) => Impact {
+            point: origin + direction * distance,
+            entity: Some(entity)
+        },
+        (None, Some(hit)) => Impact { point: hit.point, entity: None },
+        (None, None) => return None
+    };
+
+    if let Some(entity) = impact.entity {
+        enemies::hurt(game, entity, damage);
+    }
+
+    game.impacts.push(impact);
+    Some(impact)
+}
+
+/// Fires the weapon of the player while the fire key is held, as fast as
+/// the weapon goes, until it runs out of ammo.
+pub struct WeaponSystem;
+
+impl System for WeaponSystem {
+    fn update(&mut self, game: &mut Game, dt: f32) {
+        let shot = match game.world.write_storage::<Weapon>().get_mut(game.player) {
+            Some(weapon) => {
+                weapon.reload = (weapon.reload - dt).max(0.0);
+
+                if game.input.fire && weapon.reload <= 0.0 && weapon.ammo != Some(0) {
+                    weapon.reload = weapon.cooldown;
+                    weapon.ammo = weapon.ammo.map(|rounds| rounds - 1);
+                    Some((weapon.damage, weapon.range))
+                } else {
+                    None
+                }
+            }
+            None => None
+        };
+
+        if let Some((damage, range)) = shot {
+            let player = game.player;
+
+            fire(game, player, damage, range);
+        }
+    }
+}
+
+/// Distance along a ray on the floor plane to where it enters a circle.
+/// * `direction`: unit direction of the ray.
+/// Returns: `None` when the ray misses the circle, or it lies behind.
+fn ray_circle(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let to_center = Vec3::new(center.x - origin.x, center.y - origin.y, 0.0);
+    let along = glm::dot(&to_center, &direction);
+    let off_squared = glm::dot(&to_center, &to_center) - along * along;
+
+    if along < 0.0 || off_squared > radius * radius {
+        return None;
+    }
+
+    Some((along - (radius * radius - off_squared).sqrt()).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use crate::game::{
+        components::{ Enemy, EnemyState, Health },
+        enemies::{ spawn, ENEMY_HEALTH, ENEMY_RADIUS },
+        input::InputState
+    };
+    use crate::uglythings::build_experimental_game;
+
+    fn health(game: &Game, entity: Entity) -> i32 {
+        game.world.read_storage::<Health>().get(entity).unwrap().points
+    }
+
+    #[test]
+    fn stops_at_the_first_wall() {
+        let mut game = build_experimental_game();
+        let player = game.player;
+        let impact = fire(&mut game, player, 10, 100.0).unwrap();
+
+        assert!(impact.entity.is_none());
+        assert!((impact.point.x - 5.5).abs() < 1e-4);
+        assert_eq!(game.impacts.len(), 1);
+        assert!(fire(&mut game, player, 10, 3.0).is_none());
+    }
+
+    #[test]
+    fn hits_the_nearest_collider() {
+        let mut game = build_experimental_game();
+        let player = game.player;
+        let near = spawn(&mut game, Vec3::new(3.0, 8.1, 0.0), PI, Vec::new());
+        let far = spawn(&mut game, Vec3::new(4.5, 8.0, 0.0), PI, Vec::new());
+        let impact = fire(&mut game, player, 10, 100.0).unwrap();
+
+        assert_eq!(impact.entity, Some(near));
+        assert!(impact.point.x < 3.0 && impact.point.x > 3.0 - ENEMY_RADIUS);
+        assert_eq!(health(&game, near), ENEMY_HEALTH - 10);
+        assert_eq!(health(&game, far), ENEMY_HEALTH);
+
+        // The dead stop getting in the way.
+        fire(&mut game, player, ENEMY_HEALTH, 100.0);
+        assert_eq!(fire(&mut game, player, 10, 100.0).unwrap().entity, Some(far));
+    }
+
+    #[test]
+    fn steps_through_fire_and_reload_frames() {
+        let model = ViewModel {
+            image: String::new(),
+            frames: 4,
+            first_layer: 10,
+            fire: vec![1, 2],
+            reload: vec![3]
+        };
+        let mut weapon = Weapon::pistol();
+
+        assert_eq!(model.frame_layer(&weapon), 10);
+
+        let layers: Vec<_> = [1.0, 0.7, 0.4, 0.01].iter()
+            .map(|left| {
+                weapon.reload = weapon.cooldown * left;
+                model.frame_layer(&weapon)
+            })
+            .collect();
+
+        assert_eq!(layers, vec![11, 12, 13, 13]);
+    }
+
+    #[test]
+    fn fires_while_held_at_the_weapon_pace() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(4.0, 8.0, 0.0), PI, Vec::new());
+        let input = InputState { fire: true, ..InputState::default() };
+        let dt = 1.0 / 60.0;
+
+        // A shot right away, one more once the weapon is ready again.
+        for _ in 0..((PISTOL_COOLDOWN / dt) as usize + 2) {
+            game.tick(dt, &input);
+        }
+
+        assert_eq!(health(&game, guard), ENEMY_HEALTH - 2 * PISTOL_DAMAGE);
+        assert_eq!(
+            game.world.read_storage::<Enemy>().get(guard).unwrap().state,
+            EnemyState::Pain
+        );
+        assert_eq!(
+            game.world.read_storage::<Weapon>().get(game.player).unwrap().ammo,
+            Some(PISTOL_AMMO - 2)
+        );
+    }
+
+    #[test]
+    fn stops_firing_when_out_of_ammo() {
+        let mut game = build_experimental_game();
+        let guard = spawn(&mut game, Vec3::new(4.0, 8.0, 0.0), PI, Vec::new());
+        let input = InputState { fire: true, ..InputState::default() };
+
+        game.world.write_storage::<Weapon>().get_mut(game.player).unwrap().ammo = Some(1);
+
+        for _ in 0..((PISTOL_COOLDOWN * 60.0) as usize * 3) {
+            game.tick(1.0 / 60.0, &input);
+        }
+
+        assert_eq!(health(&game, guard), ENEMY_HEALTH - PISTOL_DAMAGE);
+        assert_eq!(game.world.read_storage::<Weapon>().get(game.player).unwrap().ammo, Some(0));
+    }
+}
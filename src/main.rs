@@ -3,17 +3,40 @@ extern crate nalgebra_glm as glm;
 
 mod rendering;
 mod game;
+mod controls;
+mod settings;
+#[cfg(test)]
 mod uglythings;
 
-use glm::{Vec3};
 use sdl2::{
     image::InitFlag,
-    event::Event,
+    event::{ Event, WindowEvent },
     keyboard::Keycode
 };
-use rendering::graphics::{ GlEngine, ViewSettings };
+use rendering::graphics::{
+    GlEngine, Model, RenderElement, Sprite, DirectionalSprite, ViewSettings
+};
+use rendering::{
+    automap::{ Automap, PAN_STEP, ZOOM_STEP },
+    debug::{ DebugOverlay, FrameStats, FRAME_WINDOW },
+    hud::{ FacePortrait, HudStatus },
+    impacts::ImpactEffects,
+    view_model::{ ViewModelBob, WeaponView }
+};
+use controls::Controls;
+use settings::{ Settings, WindowMode };
+use glm::Vec3;
+use specs::{ Join, WorldExt };
+use game::{
+    components::{ self, Position, Velocity, Facing, Camera, Weapon, Player },
+    input::Action,
+    model::Game,
+    movement::WALK_SPEED,
+    textures::TextureRegistry,
+    timestep::FixedTimestep
+};
 
-use std::time::{ SystemTime, Duration };
+use std::time::Instant;
 
 fn find_sdl_gl_driver() -> Option<u32> {
     for (index, item) in sdl2::render::drivers().enumerate() {
@@ -24,27 +47,167 @@ fn find_sdl_gl_driver() -> Option<u32> {
     None
 }
 
+/// The camera sits in the eyes of the entity tagged with `Camera`.
+fn camera_view(game: &Game) -> ViewSettings {
+    let positions = game.world.read_storage::<Position>();
+    let facings = game.world.read_storage::<Facing>();
+    let cameras = game.world.read_storage::<Camera>();
+    let (pos, facing, camera) = (&positions, &facings, &cameras).join().next()
+        .expect("no entity carries the camera");
+
+    ViewSettings {
+        pos: pos.0,
+        facing: facing.direction,
+        height: camera.height
+    }
+}
+
+/// Everything with a sprite, as the renderer wants it.
+fn render_elements(game: &Game, registry: &TextureRegistry) -> Vec<RenderElement> {
+    let positions = game.world.read_storage::<Position>();
+    let facings = game.world.read_storage::<Facing>();
+    let sprites = game.world.read_storage::<components::Sprite>();
+    let directional_sprites = game.world.read_storage::<components::DirectionalSprite>();
+    let facing_of = |facing: Option<&Facing>| facing
+        .map_or(Vec3::new(1.0, 0.0, 0.0), |f| f.direction);
+
+    let still = (&positions, facings.maybe(), &sprites).join()
+        .map(|(pos, facing, sprite)| RenderElement {
+            model: Model::Sprite(Sprite { layer: sprite.texture }),
+            pos: pos.0,
+            facing: facing_of(facing)
+        });
+    let directional = (&positions, facings.maybe(), &directional_sprites).join()
+        .map(|(pos, facing, sprite)| RenderElement {
+            model: Model::DirectionalSprite(DirectionalSprite {
+                first_layer: registry.sheets[sprite.sheet]
+                    .frame_layer(sprite.animation, sprite.time)
+            }),
+            pos: pos.0,
+            facing: facing_of(facing)
+        });
+
+    still.chain(directional).collect()
+}
+
+/// The weapon of the player, as the view model shows it.
+fn weapon_view(game: &Game, registry: &TextureRegistry, bob: &ViewModelBob) -> Option<WeaponView> {
+    let model = registry.view_model.as_ref()?;
+    let weapons = game.world.read_storage::<Weapon>();
+    let weapon = weapons.get(game.player)?;
+
+    Some(WeaponView {
+        layer: model.frame_layer(weapon),
+        bob: bob.offset()
+    })
+}
+
+/// What the status bar shows of the player.
+fn hud_status(game: &Game, face: &FacePortrait) -> HudStatus {
+    let players = game.world.read_storage::<Player>();
+    let player = players.get(game.player);
+
+    HudStatus {
+        health: game.player_health(),
+        armor: player.map_or(0, |player| player.armor),
+        ammo: game.world.read_storage::<Weapon>().get(game.player)
+            .and_then(|weapon| weapon.ammo),
+        keys: player.map(|player| player.keys.clone()).unwrap_or_default(),
+        look: face.look()
+    }
+}
+
+/// Zooms, pans and toggles the automap as asked since the previous frame.
+fn update_automap(automap: &mut Automap, controls: &mut Controls) {
+    if controls.take_press(Action::Map) {
+        automap.toggle();
+    }
+
+    if controls.take_press(Action::MapFullScreen) {
+        automap.toggle_full_screen();
+    }
+
+    let moves = [
+        Action::MapZoomIn, Action::MapZoomOut, Action::MapPanUp, Action::MapPanDown,
+        Action::MapPanLeft, Action::MapPanRight, Action::MapRecenter
+    ];
+
+    for &action in moves.iter() {
+        if !controls.take_press(action) || !automap.visible {
+            continue;
+        }
+
+        match action {
+            Action::MapZoomIn => automap.zoom(ZOOM_STEP),
+            Action::MapZoomOut => automap.zoom(1.0 / ZOOM_STEP),
+            Action::MapPanUp => automap.pan(0.0, PAN_STEP),
+            Action::MapPanDown => automap.pan(0.0, -PAN_STEP),
+            Action::MapPanLeft => automap.pan(-PAN_STEP, 0.0),
+            Action::MapPanRight => automap.pan(PAN_STEP, 0.0),
+            _ => automap.recenter()
+        }
+    }
+}
+
+/// How fast the player walks, from 0 to 1.
+fn walking_speed(game: &Game) -> f32 {
+    game.world.read_storage::<Velocity>().get(game.player)
+        .map_or(0.0, |velocity| velocity.0.norm() / WALK_SPEED)
+}
+
 fn main() {
     let sdl_context = sdl2::init().unwrap();
     sdl2::image::init(InitFlag::PNG).unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem.window("Window", 800, 600)
-        .opengl()
-        .build()
-        .unwrap();
+    let settings = match Settings::from_file("settings.ron") {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    };
+    let mut window_builder = video_subsystem.window("Dungeonstein", settings.width, settings.height);
 
-    let mut canvas = match window.into_canvas().index(find_sdl_gl_driver().unwrap())
-        .build() {
+    window_builder.opengl().resizable();
+
+    match settings.window_mode {
+        WindowMode::Windowed => {},
+        WindowMode::Fullscreen => { window_builder.fullscreen(); },
+        WindowMode::Borderless => { window_builder.fullscreen_desktop(); }
+    }
+
+    let window = match window_builder.build() {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    };
+    let mut canvas_builder = window.into_canvas().index(find_sdl_gl_driver().unwrap());
+
+    if settings.vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+
+    let mut canvas = match canvas_builder.build() {
         Ok(x) => x,
         Err(e) => panic!("{}", e)
     };
     gl::load_with(|name| video_subsystem.gl_get_proc_address(name) as *const _);
     let mut event_pump = sdl_context.event_pump().unwrap();
+    // Hides the cursor and reports mouse motion past the window borders.
+    sdl_context.mouse().set_relative_mouse_mode(true);
     canvas.window().gl_set_context_to_current().unwrap();
-    let texture_creator = canvas.texture_creator();
+    let registry = match TextureRegistry::from_file("textures/textures.ron") {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    };
+    // The window may not get the size asked for, in full screen above all.
+    let (width, height) = canvas.window().drawable_size();
     // Push the previous code into the Engine??
-    let mut renderer = GlEngine::new(&texture_creator);
-    let mut game = uglythings::build_experimental_game();
+    let mut renderer = GlEngine::new(
+        &registry, (width as f32, height as f32), settings.field_of_view
+    );
+    let level_path = std::env::args().nth(1)
+        .unwrap_or_else(|| "levels/level00.ron".to_string());
+    let mut game = match game::level::load_level(&level_path, &registry) {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    };
 
     unsafe {
         gl::Enable(gl::CULL_FACE);
@@ -53,8 +216,21 @@ fn main() {
         gl::DepthFunc(gl::LESS);
     }
 
-    let mut frames = 0i32;
-    let mut start = SystemTime::now();
+    let mut last_frame = Instant::now();
+    let mut timestep = FixedTimestep::new(60.0);
+    let mut controls = match Controls::from_file(
+        "controls.ron", sdl_context.game_controller().unwrap()
+    ) {
+        Ok(x) => x,
+        Err(e) => panic!("{}", e)
+    };
+    let mut previous_view = camera_view(&game);
+    let mut impacts = ImpactEffects::new(registry.impact);
+    let mut bob = ViewModelBob::default();
+    let mut face = FacePortrait::default();
+    let mut debug = DebugOverlay::default();
+    let mut automap = Automap::default();
+    let mut frame_stats = FrameStats::new(FRAME_WINDOW);
 
     'running: loop {
         canvas.window().gl_set_context_to_current().unwrap();
@@ -64,24 +240,26 @@ fn main() {
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
         }
 
+        let mut elements = render_elements(&game, &registry);
+        elements.extend(impacts.render_elements());
+
         renderer.render(
-            &game.current_map, ViewSettings {
-                pos: game.player.pos,
-                facing: game.player.direction,
-                height: 1.77
-            }
+            &game.current_map,
+            &elements,
+            previous_view.lerp(&camera_view(&game), timestep.alpha()),
+            weapon_view(&game, &registry, &bob).as_ref()
         );
-        canvas.present();
-
-        frames += 1;
-
-        if start.elapsed().unwrap_or(Duration::from_secs(0)).as_secs() >= 10 {
-            println!("{} FPS", frames / 10);
-            start = SystemTime::now();
-            frames = 0;
+        renderer.draw_hud(&hud_status(&game, &face));
+        if let Some(player) = game.world.read_storage::<Player>().get(game.player) {
+            renderer.draw_automap(
+                &game.current_map, &player.explored, game.player_position(),
+                game.player_facing().direction, &automap
+            );
         }
-
-        game.player.speed = Vec3::new(0.0, 0.0, 0.0);
+        debug.draw(
+            &mut renderer, &frame_stats, game.player_position(), game.player_facing().angle
+        );
+        canvas.present();
 
         for event in event_pump.poll_iter() {
             match event {
@@ -92,33 +270,45 @@ fn main() {
                     break 'running
                 },
                 Event::KeyDown {
-                    keycode: Some(Keycode::Up), ..
+                    keycode: Some(Keycode::F3), repeat: false, ..
+                } => debug.toggle(),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..), ..
                 } => {
-                    game.player.speed = game.player.direction * 0.75;
-                },
-                Event::KeyDown {
-                    keycode: Some(Keycode::Down), ..
-                } => {
-                    game.player.speed = -game.player.direction * 0.75;
+                    let (width, height) = canvas.window().drawable_size();
+
+                    renderer.set_screen_size(width as f32, height as f32);
                 },
-                Event::KeyDown {
-                    keycode: Some(Keycode::Left), ..
-                } => {
-                    game.player.angle += 3.14 / 16.0;
+                Event::MouseWheel { y, .. } if automap.visible => {
+                    automap.zoom(ZOOM_STEP.powi(y));
                 },
-                Event::KeyDown {
-                    keycode: Some(Keycode::Right), ..
-                } => {
-                    game.player.angle -= 3.14 / 16.0;
+                _ => {
+                    if let Err(e) = controls.handle_event(&event) {
+                        debug.report(e);
+                    }
                 }
-                _ => {}
             }
+        }
 
-            game.player.direction = glm::rotate_z_vec3(
-                &Vec3::new(1.0, 0.0, 0.0), game.player.angle
-            );
+        controls.update(&event_pump);
+        update_automap(&mut automap, &mut controls);
+
+        let frame_time = last_frame.elapsed().as_secs_f32();
+        last_frame = Instant::now();
+        frame_stats.push(frame_time);
+
+        for _ in 0..timestep.advance(frame_time) {
+            previous_view = camera_view(&game);
+            game.tick(timestep.step(), &controls.input_state());
+            controls.consume();
+
+            for impact in game.impacts.drain(..) {
+                impacts.spawn(impact.point, &previous_view);
+            }
         }
 
-        game.player.pos += game.player.speed;
+        impacts.update(frame_time);
+        bob.update(walking_speed(&game), frame_time);
+        face.update(game.player_health(), frame_time);
     }
 }
@@ -0,0 +1,401 @@
+//! Top-down map of the cells the player has seen, drawn in flat colors over
+//! the screen: in a corner, or over the whole view.
+//!
+//! The map is centered on the player, north up: `x` grows to the right and
+//! `y` upward, like the cells.
+
+use gl::types::{ GLint, GLuint, GLenum };
+use glm::Vec3;
+use crate::game::{
+    doors,
+    model::{ DungeonFloor, DungeonCell::{ Floor, Door, OpenDoor } }
+};
+use crate::rendering::{
+    engine_types::Uniforms,
+    glwrap::{ GlShaderProgram, GlShader, GlVertexArray, Bind }
+};
+
+/// Pixels per cell the map starts at.
+pub const DEFAULT_ZOOM: f32 = 12.0;
+pub const MIN_ZOOM: f32 = 4.0;
+pub const MAX_ZOOM: f32 = 48.0;
+/// Zoom factor of a step in or out.
+pub const ZOOM_STEP: f32 = 1.25;
+/// Cells the map moves by on a pan step.
+pub const PAN_STEP: f32 = 2.0;
+/// Side of the map in its corner, in pixels.
+const CORNER_SIZE: f32 = 200.0;
+/// Pixels between the map in its corner and the borders of the screen.
+const MARGIN: f32 = 8.0;
+/// Length of the player arrow, in cells.
+const ARROW_SIZE: f32 = 0.8;
+
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const FLOOR_COLOR: [f32; 4] = [0.2, 0.22, 0.3, 0.9];
+const WALL_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const DOOR_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const PLAYER_COLOR: [f32; 4] = [1.0, 0.25, 0.2, 1.0];
+
+/// A rectangle of the screen, in pixels from its bottom left corner.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32
+}
+
+/// How the map shows: whether at all, how large and where.
+#[derive(Clone, Debug)]
+pub struct Automap {
+    pub visible: bool,
+    /// Covers the whole view rather than a corner.
+    pub full_screen: bool,
+    /// Pixels per cell.
+    zoom: f32,
+    /// How far the middle of the map is from the player, in cells.
+    pan: (f32, f32)
+}
+
+impl Default for Automap {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            full_screen: false,
+            zoom: DEFAULT_ZOOM,
+            pan: (0.0, 0.0)
+        }
+    }
+}
+
+impl Automap {
+    /// Shows or hides the map, centered on the player again.
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.recenter();
+    }
+
+    /// Switches between the corner and the whole view, showing the map
+    /// when hidden.
+    pub fn toggle_full_screen(&mut self) {
+        if self.visible {
+            self.full_screen = !self.full_screen;
+        } else {
+            self.visible = true;
+            self.full_screen = true;
+            self.recenter();
+        }
+    }
+
+    /// * `factor`: above 1 to zoom in, below 1 to zoom out.
+    pub fn zoom(&mut self, factor: f32) {
+        self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Moves the middle of the map.
+    /// * `dx`, `dy`: in cells, right and up.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.pan = (self.pan.0 + dx, self.pan.1 + dy);
+    }
+
+    pub fn recenter(&mut self) {
+        self.pan = (0.0, 0.0);
+    }
+
+    /// Where the map shows on a screen.
+    /// * `view`: the part of the screen the world is drawn in.
+    pub fn area(&self, view: Area) -> Area {
+        if self.full_screen {
+            return view;
+        }
+
+        let size = CORNER_SIZE.min(view.width - 2.0 * MARGIN).min(view.height - 2.0 * MARGIN);
+
+        Area {
+            x: view.x + view.width - MARGIN - size,
+            y: view.y + view.height - MARGIN - size,
+            width: size,
+            height: size
+        }
+    }
+}
+
+/// Shapes of the map, in pixels, two floats a vertex.
+#[derive(Clone, Default, Debug)]
+pub struct AutomapGeometry {
+    /// Triangles behind the map.
+    pub background: Vec<f32>,
+    /// Triangles of the walkable cells seen.
+    pub floors: Vec<f32>,
+    /// Segments where walkable cells seen meet solid ones.
+    pub walls: Vec<f32>,
+    /// Segments across the doors seen, along their panels.
+    pub doors: Vec<f32>,
+    /// Triangle pointing where the player looks.
+    pub player: Vec<f32>
+}
+
+/// Builds the shapes of the map, leaving out the cells outside its area.
+/// * `explored`: cells seen so far, by cell index.
+/// * `pos`, `direction`: where the player stands and looks, in cells.
+/// * `area`: where the map shows, see `Automap::area`.
+pub fn build_automap_geometry(
+    floor: &DungeonFloor, explored: &[bool], pos: Vec3, direction: Vec3,
+    automap: &Automap, area: Area
+) -> AutomapGeometry {
+    let mut geometry = AutomapGeometry::default();
+    let zoom = automap.zoom;
+    let center = (
+        area.x + area.width / 2.0 - (pos.x + automap.pan.0) * zoom,
+        area.y + area.height / 2.0 - (pos.y + automap.pan.1) * zoom
+    );
+    let to_screen = |x: f32, y: f32| (center.0 + x * zoom, center.1 + y * zoom);
+    let walkable = |x: i32, y: i32| {
+        matches!(floor.cell(x, y), Some(Floor(_)) | Some(Door) | Some(OpenDoor))
+    };
+
+    push_quad(
+        &mut geometry.background, (area.x, area.y), (area.x + area.width, area.y + area.height)
+    );
+
+    for (index, _) in explored.iter().enumerate().filter(|(_, seen)| **seen) {
+        let (x, y) = ((index % floor.width) as i32, (index / floor.width) as i32);
+        let low = to_screen(x as f32 - 0.5, y as f32 - 0.5);
+        let high = to_screen(x as f32 + 0.5, y as f32 + 0.5);
+
+        if !walkable(x, y) || high.0 < area.x || high.1 < area.y
+            || low.0 > area.x + area.width || low.1 > area.y + area.height {
+            continue;
+        }
+
+        push_quad(&mut geometry.floors, low, high);
+
+        for &((dx, dy), from, to) in [
+            ((0, -1), (low.0, low.1), (high.0, low.1)),
+            ((1, 0), (high.0, low.1), (high.0, high.1)),
+            ((0, 1), (high.0, high.1), (low.0, high.1)),
+            ((-1, 0), (low.0, high.1), (low.0, low.1))
+        ].iter() {
+            if !walkable(x + dx, y + dy) {
+                geometry.walls.extend_from_slice(&[from.0, from.1, to.0, to.1]);
+            }
+        }
+
+        if floor.cell(x, y) == Some(Door) || floor.cell(x, y) == Some(OpenDoor) {
+            let middle = to_screen(x as f32, y as f32);
+            let segment = if doors::spans_x(floor, x, y) {
+                [low.0, middle.1, high.0, middle.1]
+            } else {
+                [middle.0, low.1, middle.0, high.1]
+            };
+
+            geometry.doors.extend_from_slice(&segment);
+        }
+    }
+
+    let forward = Vec3::new(direction.x, direction.y, 0.0).normalize() * ARROW_SIZE;
+    let side = Vec3::new(-forward.y, forward.x, 0.0) * 0.4;
+    let (x, y) = (pos.x, pos.y);
+
+    for corner in [
+        to_screen(x + forward.x * 0.6, y + forward.y * 0.6),
+        to_screen(x - forward.x * 0.4 + side.x, y - forward.y * 0.4 + side.y),
+        to_screen(x - forward.x * 0.4 - side.x, y - forward.y * 0.4 - side.y)
+    ].iter() {
+        geometry.player.extend_from_slice(&[corner.0, corner.1]);
+    }
+
+    geometry
+}
+
+/// Two triangles covering a rectangle.
+/// * `low`, `high`: bottom left and top right corners.
+fn push_quad(triangles: &mut Vec<f32>, low: (f32, f32), high: (f32, f32)) {
+    triangles.extend_from_slice(&[
+        low.0, low.1, high.0, low.1, high.0, high.1,
+        low.0, low.1, high.0, high.1, low.0, high.1
+    ]);
+}
+
+/// Draws the map in flat colors.
+pub struct AutomapGraphics {
+    shader_program: GlShaderProgram,
+    uniforms: Uniforms,
+    u_color: GLint,
+    vertex_array: GlVertexArray
+}
+
+impl AutomapGraphics {
+    pub fn new() -> Self {
+        let a_position: GLuint = 0;
+        let shaders = vec![
+            GlShader::from_file("shaders/2dvertexShader.glsl")
+                .unwrap(),
+            GlShader::from_file("shaders/colorFragmentShader.glsl")
+                .unwrap()
+        ];
+        let shader_program = GlShaderProgram::new(
+            &shaders, &attrib_bindings![a_position]
+        ).unwrap();
+        let uniforms = Uniforms::from_program(&shader_program);
+        let u_color = shader_program.uniform_location("u_color\0");
+
+        Self {
+            shader_program,
+            uniforms,
+            u_color,
+            vertex_array: GlVertexArray::from_vertex_buffer(&[], &[], &[(a_position, 2)])
+        }
+    }
+
+    /// Draws the map over whatever the screen holds, cut to its area.
+    /// * `width`, `height`: size of the screen in pixels.
+    /// Returns: the draw calls issued.
+    pub fn draw(&self, geometry: &AutomapGeometry, area: Area, width: f32, height: f32) -> usize {
+        let _prg_bind = Bind::new(&self.shader_program);
+        let projection_matrix: [[f32; 4]; 4] = glm::ortho(
+            0.0, width, 0.0, height, -1.0, 1.0
+        ).into();
+        let model_matrix: [[f32; 4]; 4] = glm::identity::<f32, glm::U4>().into();
+
+        unsafe {
+            gl::UniformMatrix4fv(self.uniforms.u_projection_matrix, 1, gl::FALSE,
+                &projection_matrix[0][0] as *const f32);
+            gl::UniformMatrix4fv(self.uniforms.u_model_matrix, 1, gl::FALSE,
+                &model_matrix[0][0] as *const f32);
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(area.x as i32, area.y as i32, area.width as i32, area.height as i32);
+        }
+
+        let calls = [
+            (&geometry.background, gl::TRIANGLES, BACKGROUND_COLOR),
+            (&geometry.floors, gl::TRIANGLES, FLOOR_COLOR),
+            (&geometry.walls, gl::LINES, WALL_COLOR),
+            (&geometry.doors, gl::LINES, DOOR_COLOR),
+            (&geometry.player, gl::TRIANGLES, PLAYER_COLOR)
+        ].iter()
+            .map(|(vertices, mode, color)| self.draw_shapes(vertices, *mode, *color))
+            .sum();
+
+        unsafe {
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::CULL_FACE);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        calls
+    }
+
+    /// Returns: the draw calls issued.
+    fn draw_shapes(&self, vertices: &[f32], mode: GLenum, color: [f32; 4]) -> usize {
+        if vertices.is_empty() {
+            return 0;
+        }
+
+        self.vertex_array.update(vertices, &[]);
+
+        let _vertex_bind = Bind::new(&self.vertex_array);
+
+        unsafe {
+            gl::Uniform4f(self.u_color, color[0], color[1], color[2], color[3]);
+            gl::DrawArrays(mode, 0, (vertices.len() / 2) as i32);
+        }
+
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::model::DungeonCell::{ self, Wall };
+
+    const W: DungeonCell = Wall(0);
+    const F: DungeonCell = Floor(1);
+    const SCREEN: Area = Area { x: 0.0, y: 0.0, width: 800.0, height: 600.0 };
+
+    fn corridor() -> DungeonFloor {
+        DungeonFloor::new(5, 3, vec![
+            W, W, W, W, W,
+            W, F, Door, F, W,
+            W, W, W, W, W
+        ])
+    }
+
+    /// Segments, as pairs of points.
+    fn segments(lines: &[f32]) -> Vec<[f32; 4]> {
+        lines.chunks(4).map(|s| [s[0], s[1], s[2], s[3]]).collect()
+    }
+
+    fn near(point: &[f32], x: f32, y: f32) -> bool {
+        (point[0] - x).abs() < 1e-3 && (point[1] - y).abs() < 1e-3
+    }
+
+    #[test]
+    fn outlines_what_was_seen() {
+        let floor = corridor();
+        let mut explored = vec![false; 15];
+        let automap = Automap { full_screen: true, ..Automap::default() };
+
+        // The first floor and the door, not the floor past it.
+        explored[6] = true;
+        explored[7] = true;
+
+        let geometry = build_automap_geometry(
+            &floor, &explored, Vec3::new(1.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0),
+            &automap, SCREEN
+        );
+
+        assert_eq!(geometry.floors.len(), 2 * 12);
+        // Three sides of the dead end, two of the door.
+        assert_eq!(geometry.walls.len(), 5 * 4);
+
+        // The door sits between walls above and below, its panel spans y.
+        let door = segments(&geometry.doors)[0];
+        let x = 400.0 + DEFAULT_ZOOM;
+        assert_eq!(door, [x, 300.0 - DEFAULT_ZOOM / 2.0, x, 300.0 + DEFAULT_ZOOM / 2.0]);
+
+        // The arrow points east, from the middle of the map.
+        assert!(near(&geometry.player, 400.0 + ARROW_SIZE * 0.6 * DEFAULT_ZOOM, 300.0));
+    }
+
+    #[test]
+    fn pans_and_zooms() {
+        let floor = corridor();
+        let explored = vec![true; 15];
+        let mut automap = Automap::default();
+
+        automap.toggle();
+        automap.pan(PAN_STEP, 0.0);
+        automap.pan(PAN_STEP, 0.0);
+        for _ in 0..20 {
+            automap.zoom(ZOOM_STEP);
+        }
+
+        let area = automap.area(SCREEN);
+        assert_eq!(area, Area { x: 592.0, y: 392.0, width: 200.0, height: 200.0 });
+
+        let geometry = build_automap_geometry(
+            &floor, &explored, Vec3::new(1.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0),
+            &automap, area
+        );
+
+        // Panned east at the largest zoom, only the last floor is left in
+        // the map, and the player out of it.
+        assert_eq!(geometry.floors.len(), 12);
+        assert!(near(
+            &geometry.player,
+            692.0 - 2.0 * PAN_STEP * MAX_ZOOM, 492.0 + ARROW_SIZE * 0.6 * MAX_ZOOM
+        ));
+
+        automap.toggle();
+        automap.toggle_full_screen();
+        assert!(automap.visible && automap.full_screen);
+        assert_eq!(automap.area(SCREEN), SCREEN);
+    }
+}
@@ -0,0 +1,181 @@
+//! Debug overlay: frame rate, frame times and what the renderer went through
+//! to draw the latest frame, shown at the top left of the screen.
+
+use std::collections::VecDeque;
+use glm::Vec3;
+use crate::rendering::graphics::{ GlEngine, RenderStats };
+
+/// Frames the frame times are gathered over.
+pub const FRAME_WINDOW: usize = 120;
+/// Pixels between the text and the borders of the screen.
+const MARGIN: f32 = 8.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 0.4, 1.0];
+const SHADOW_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+/// Times of the latest frames.
+pub struct FrameStats {
+    /// In seconds, newest last.
+    times: VecDeque<f32>,
+    window: usize
+}
+
+impl FrameStats {
+    /// * `window`: how many of the latest frames to keep.
+    pub fn new(window: usize) -> Self {
+        Self {
+            times: VecDeque::with_capacity(window),
+            window: window.max(1)
+        }
+    }
+
+    /// * `dt`: seconds the frame took.
+    pub fn push(&mut self, dt: f32) {
+        if self.times.len() == self.window {
+            self.times.pop_front();
+        }
+
+        self.times.push_back(dt);
+    }
+
+    /// Frames per second over the window, 0 before the first frame.
+    pub fn fps(&self) -> f32 {
+        let total: f32 = self.times.iter().sum();
+
+        if total > 0.0 { self.times.len() as f32 / total } else { 0.0 }
+    }
+
+    /// Shortest, average and longest frame time over the window, in
+    /// seconds.
+    pub fn min_avg_max(&self) -> (f32, f32, f32) {
+        if self.times.is_empty() {
+            return (0.0, 0.0, 0.0);
+        }
+
+        let total: f32 = self.times.iter().sum();
+
+        (
+            self.times.iter().cloned().fold(f32::INFINITY, f32::min),
+            total / self.times.len() as f32,
+            self.times.iter().cloned().fold(0.0, f32::max)
+        )
+    }
+}
+
+/// The lines of the overlay.
+/// * `pos`: where the player stands, in cells.
+/// * `angle`: where the player looks, in radians.
+pub fn debug_text(frames: &FrameStats, render: &RenderStats, pos: Vec3, angle: f32) -> String {
+    let (min, avg, max) = frames.min_avg_max();
+
+    format!(
+        "FPS {:.0}\nFrame {:.1} / {:.1} / {:.1} ms\nDraw calls {}\nCells {}\nPos {:.2}, {:.2}\nAngle {:.0}",
+        frames.fps(),
+        min * 1000.0, avg * 1000.0, max * 1000.0,
+        render.draw_calls,
+        render.cells,
+        pos.x, pos.y,
+        angle.to_degrees().rem_euclid(360.0)
+    )
+}
+
+/// Shows the debug text over the screen, while visible.
+#[derive(Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+    /// Latest problem reported, shown under the stats.
+    error: Option<String>
+}
+
+impl DebugOverlay {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Keeps an error to show, replacing the previous one.
+    pub fn report(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    /// The lines of the overlay, with the latest error if any.
+    /// * `pos`, `angle`: see `debug_text`.
+    pub fn text(&self, frames: &FrameStats, render: &RenderStats, pos: Vec3, angle: f32) -> String {
+        let text = debug_text(frames, render, pos, angle);
+
+        match &self.error {
+            Some(error) => format!("{}\nError: {}", text, error),
+            None => text
+        }
+    }
+
+    /// Draws the overlay, over whatever the screen holds, when visible.
+    /// * `pos`, `angle`: see `debug_text`.
+    pub fn draw(&self, engine: &mut GlEngine, frames: &FrameStats, pos: Vec3, angle: f32) {
+        if !self.visible {
+            return;
+        }
+
+        let text = self.text(frames, &engine.stats(), pos, angle);
+        let (_, height) = engine.screen_size();
+        let top = height - MARGIN - engine.line_height(1.0);
+
+        // A shadow keeps the text readable over bright walls.
+        engine.draw_text((MARGIN + 1.0, top - 1.0), 1.0, SHADOW_COLOR, &text);
+        engine.draw_text((MARGIN, top), 1.0, TEXT_COLOR, &text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_frames() {
+        let mut frames = FrameStats::new(4);
+
+        assert_eq!(frames.fps(), 0.0);
+        assert_eq!(frames.min_avg_max(), (0.0, 0.0, 0.0));
+
+        for dt in [0.5, 0.01, 0.02, 0.02, 0.03].iter() {
+            frames.push(*dt);
+        }
+
+        // The slow first frame left the window.
+        let (min, avg, max) = frames.min_avg_max();
+        assert!((min - 0.01).abs() < 1e-6);
+        assert!((avg - 0.02).abs() < 1e-6);
+        assert!((max - 0.03).abs() < 1e-6);
+        assert!((frames.fps() - 50.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reports_the_frame() {
+        let mut frames = FrameStats::new(FRAME_WINDOW);
+        frames.push(0.02);
+
+        let render = RenderStats { draw_calls: 4, cells: 78 };
+        let text = debug_text(&frames, &render, Vec3::new(1.0, 8.5, 0.0), -std::f32::consts::FRAC_PI_2);
+
+        assert_eq!(
+            text,
+            "FPS 50\nFrame 20.0 / 20.0 / 20.0 ms\nDraw calls 4\nCells 78\nPos 1.00, 8.50\nAngle 270"
+        );
+    }
+
+    #[test]
+    fn shows_the_latest_error() {
+        let frames = FrameStats::new(FRAME_WINDOW);
+        let render = RenderStats { draw_calls: 0, cells: 0 };
+        let mut overlay = DebugOverlay::default();
+        let stats = debug_text(&frames, &render, Vec3::zeros(), 0.0);
+
+        assert_eq!(overlay.text(&frames, &render, Vec3::zeros(), 0.0), stats);
+
+        overlay.report("cannot open controller 0".to_string());
+        overlay.report("cannot open controller 1".to_string());
+
+        assert_eq!(
+            overlay.text(&frames, &render, Vec3::zeros(), 0.0),
+            format!("{}\nError: cannot open controller 1", stats)
+        );
+    }
+}
@@ -8,6 +8,7 @@ use gl::types::{
     GLsizeiptr
 };
 use sdl2::{
+    pixels::PixelFormatEnum,
     surface::Surface,
     image::{LoadSurface, LoadTexture},
     render::{Canvas, Texture, TextureCreator},
@@ -49,68 +50,87 @@ pub fn texture_from_file<'r, P: AsRef<Path>>(texture_creator: & 'r TextureCreato
 
     texture
 }
-/*
+/// A 2D texture.
 pub struct GlTexture {
-    handle: GLuint
+    handle: GLuint,
+    width: usize,
+    height: usize
 }
 
 impl GlTexture {
+    /// Loads an image file, top row first.
+    /// Returns: `Result<GlTexture, String>`.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let image: Surface = match LoadSurface::from_file(path) {
-            Ok(s) => s,
-            Err(e) => panic!("Failed to load image surface: {}", e.to_string()),
-        };
-
-        // Ugly hack
-        image.
-        let raw_bytes = image.buffer.as_bytes();
-        let mut inversion = Vec::with_capacity(image.width * image.height * 4);
-
-        for y in (0..image.height).rev() {
-            let start = y * image.width * 4;
-            let end = start + image.width * 4;
-            inversion.extend_from_slice(&raw_bytes[start..end]);
-        }
+        let image: Surface = LoadSurface::from_file(&path)
+            .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+        // ABGR8888 is laid out as R, G, B, A bytes on little endian.
+        let image = image.convert_format(PixelFormatEnum::ABGR8888)?;
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        let pitch = image.pitch() as usize;
+        let mut data = Vec::with_capacity(width * height * 4);
+
+        image.with_lock(|pixels| {
+            for y in 0..height {
+                data.extend_from_slice(&pixels[y * pitch..y * pitch + width * 4]);
+            }
+        });
 
-        Self::from_raw_rgba(image.width, image.height, &inversion)
+        Self::from_raw_rgba(width, height, &data)
     }
 
     pub fn from_raw_rgba(width: usize, height: usize,
         data: &[u8]) -> Result<Self, String> {
+        assert_eq!(data.len(), width * height * 4);
+
         let mut handle: GLuint = 0;
 
         unsafe {
             gl::GenTextures(1, &mut handle);
         }
 
-        let result = GlTexture { handle };
+        let result = GlTexture { handle, width, height };
 
         {
-            let mut _bind = Bind::new(&result);
+            let _bind = Bind::new(&result);
             unsafe {
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT.try_into().unwrap());
-                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT.try_into().unwrap());
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE.try_into().unwrap());
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE.try_into().unwrap());
                 gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST.try_into().unwrap());
                 gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST.try_into().unwrap());
                 gl::TexImage2D(
                     gl::TEXTURE_2D, 0, gl::RGBA as i32,
                     width as i32, height as i32, 0, gl::RGBA,
                     gl::UNSIGNED_BYTE, data.as_ptr() as *const c_void);
-                gl::GenerateMipmap(gl::TEXTURE_2D);
             }
         }
 
         Ok(result)
     }
 
-    pub fn detach(&mut self) -> GLuint {
-        let h = self.handle;
-        self.handle = 0;
-        h
+    pub fn width(&self) -> usize {
+        self.width
     }
-}*/
 
-/*
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl GlBindable for GlTexture {
+    fn bind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.handle);
+        }
+    }
+
+    fn unbind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+}
+
 impl Drop for GlTexture {
     fn drop(&mut self) {
         if self.handle == 0 { return; }
@@ -118,9 +138,126 @@ impl Drop for GlTexture {
         unsafe {
             gl::DeleteTextures(1, &self.handle);
         }
+
         self.handle = 0;
     }
-}*/
+}
+
+/// A layered 2D texture, one layer per image. Every layer shares the same
+/// size, so a single bind serves all of them.
+pub struct GlTextureArray {
+    handle: GLuint,
+    layers: usize
+}
+
+impl GlTextureArray {
+    /// Loads image files into layers. The first image gives the size of the
+    /// layers, larger images are cut in a grid of layers, left to right
+    /// then top to bottom.
+    /// * `paths`: image files, each the size of the first or a grid of it.
+    /// Returns: `Result<GlTextureArray, String>`.
+    pub fn from_files<P: AsRef<Path>>(paths: &[P]) -> Result<Self, String> {
+        let mut size: Option<(usize, usize)> = None;
+        let mut data = Vec::new();
+        let mut layers = 0;
+
+        for path in paths {
+            let image: Surface = LoadSurface::from_file(path)
+                .map_err(|e| format!("{}: {}", path.as_ref().display(), e))?;
+            // ABGR8888 is laid out as R, G, B, A bytes on little endian.
+            let image = image.convert_format(PixelFormatEnum::ABGR8888)?;
+            let width = image.width() as usize;
+            let height = image.height() as usize;
+
+            let (w, h) = *size.get_or_insert((width, height));
+
+            if width % w != 0 || height % h != 0 {
+                return Err(format!(
+                    "{}: expected a {}x{} image or a grid of them, found {}x{}",
+                    path.as_ref().display(), w, h, width, height
+                ));
+            }
+
+            let pitch = image.pitch() as usize;
+
+            image.with_lock(|pixels| {
+                for top in (0..height).step_by(h) {
+                    for left in (0..width).step_by(w) {
+                        for y in top..top + h {
+                            let start = y * pitch + left * 4;
+                            data.extend_from_slice(&pixels[start..start + w * 4]);
+                        }
+
+                        layers += 1;
+                    }
+                }
+            });
+        }
+
+        let (width, height) = size.ok_or_else(|| "Empty texture array.".to_string())?;
+
+        Self::from_raw_rgba(width, height, layers, &data)
+    }
+
+    pub fn from_raw_rgba(width: usize, height: usize, layers: usize,
+        data: &[u8]) -> Result<Self, String> {
+        assert_eq!(data.len(), width * height * layers * 4);
+
+        let mut handle: GLuint = 0;
+
+        unsafe {
+            gl::GenTextures(1, &mut handle);
+        }
+
+        let result = GlTextureArray { handle, layers };
+
+        {
+            let _bind = Bind::new(&result);
+            unsafe {
+                gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::REPEAT.try_into().unwrap());
+                gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::REPEAT.try_into().unwrap());
+                gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::NEAREST.try_into().unwrap());
+                gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::NEAREST.try_into().unwrap());
+                gl::TexImage3D(
+                    gl::TEXTURE_2D_ARRAY, 0, gl::RGBA as i32,
+                    width as i32, height as i32, layers as i32, 0, gl::RGBA,
+                    gl::UNSIGNED_BYTE, data.as_ptr() as *const c_void);
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+}
+
+impl GlBindable for GlTextureArray {
+    fn bind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.handle);
+        }
+    }
+
+    fn unbind(&self) {
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+    }
+}
+
+impl Drop for GlTextureArray {
+    fn drop(&mut self) {
+        if self.handle == 0 { return; }
+
+        unsafe {
+            gl::DeleteTextures(1, &self.handle);
+        }
+
+        self.handle = 0;
+    }
+}
 
 /// A gl shader.
 pub struct GlShader {
@@ -362,6 +499,28 @@ impl GlVertexArray {
 
         Self { handle, vertex_buffer_handle, element_buffer_handle }
     }
+
+    /// Replaces the content of the buffers, keeping the attributes, for
+    /// geometry that changes every frame.
+    /// * `data`: slice of floats to load.
+    /// * `indices`: element array indices
+    pub fn update(&self, data: &[GLfloat], indices: &[GLuint]) {
+        const SIZE_OF_FLOAT: usize = std::mem::size_of::<GLfloat>();
+        const SIZE_OF_UINT: usize = std::mem::size_of::<GLuint>();
+
+        unsafe {
+            gl::BindVertexArray(self.handle);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer_handle);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                (data.len() * SIZE_OF_FLOAT).try_into().unwrap(),
+                data.as_ptr() as *const c_void, gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.element_buffer_handle);
+            gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
+                (indices.len() * SIZE_OF_UINT).try_into().unwrap(),
+                indices.as_ptr() as *const c_void, gl::DYNAMIC_DRAW);
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl GlBindable for GlVertexArray {
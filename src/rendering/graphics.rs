@@ -2,156 +2,267 @@ use glm::{Vec3};
 use na::base::Matrix4;
 use gl::types::GLuint;
 use crate::rendering::{
+    automap::{ Area, Automap, AutomapGraphics, build_automap_geometry },
     engine_types::Uniforms,
-    glwrap,
     glwrap::{
-        GlShaderProgram, GlShader, GlVertexArray, Bind
-    }
-};
-use crate::game::model::{
-    DungeonFloor,
-    DungeonCell::{Empty, Floor, Wall}
+        GlShaderProgram, GlShader, GlTextureArray, Bind
+    },
+    hud::{ HudGraphics, HudStatus },
+    level_mesh::LevelMesh,
+    overlay::Overlay,
+    sprites::SpriteBatch,
+    text::TextRenderer,
+    view_model::{ WeaponView, build_view_model_geometry }
 };
-use sdl2::{
-    render::{ Texture, TextureCreator },
-    image::LoadTexture,
-    video::WindowContext
+use crate::game::{
+    model::{ DungeonFloor, TextureIndex },
+    textures::TextureRegistry
 };
-use slotmap::{ SlotMap, new_key_type };
 
-pub struct DungeonGraphics<'r> {
-    wall_texture: Texture<'r>,
-    floor_texture: Texture<'r>,
+pub struct DungeonGraphics {
+    /// Every texture of the registry, one layer each, in registry order.
+    textures: GlTextureArray,
     shader_program: GlShaderProgram,
     uniforms: Uniforms,
     a_position: GLuint,
     a_tex_coord: GLuint,
-    wall_vertex_array: GlVertexArray,
-    floor_vertex_array: GlVertexArray
+    a_layer: GLuint,
+    level_mesh: LevelMesh,
+    sprite_batch: SpriteBatch
 }
 
-impl<'r> DungeonGraphics<'r> {
-    pub fn new(texture_creator: & 'r TextureCreator<WindowContext>) -> Self {
+impl DungeonGraphics {
+    pub fn new(registry: &TextureRegistry) -> Self {
         let a_position: GLuint = 0;
         let a_tex_coord: GLuint = 1;
+        let a_layer: GLuint = 2;
         let shaders = vec![
-            GlShader::from_file("shaders/vertexShader.glsl")
+            GlShader::from_file("shaders/worldVertexShader.glsl")
                 .unwrap(),
-            GlShader::from_file("shaders/fragmentShader.glsl")
+            GlShader::from_file("shaders/worldFragmentShader.glsl")
                 .unwrap()
         ];
-        let shader_program = GlShaderProgram::new(&shaders, &attrib_bindings![a_position, a_tex_coord])
-            .unwrap();
-        let wall_vertex_array = GlVertexArray::from_vertex_buffer(
-            &[
-                -0.75, -0.75, 0.0,    0.0, 0.0,
-                0.75, -0.75, 0.0,    1.0, 0.0,
-                0.75, -0.75, 3.0,    1.0, 2.0,
-                -0.75, -0.75, 3.0,    0.0, 2.0,
-                0.75,  0.75, 0.0,    2.0, 0.0,
-                0.75,  0.75, 3.0,    2.0, 2.0,
-                -0.75,  0.75, 0.0,    3.0, 0.0,
-                -0.75,  0.75, 3.0,    3.0, 2.0,
-                -0.75, -0.75, 0.0,    4.0, 0.0,
-                -0.75, -0.75, 3.0,    4.0, 2.0,
-            ],
-            &[
-                0, 1, 2,
-                0, 2, 3,
-                1, 4, 5,
-                1, 5, 2,
-                4, 6, 7,
-                4, 7, 5,
-                6, 8, 9,
-                6, 9, 7,
-            ],
-            &[(a_position, 3), (a_tex_coord, 2)]
-        );
-        let floor_vertex_array = GlVertexArray::from_vertex_buffer(
-            &[
-                -0.75, -0.75, 0.0,    0.0, 0.0,
-                 0.75, -0.75, 0.0,    1.0, 0.0,
-                 0.75,  0.75, 0.0,    1.0, 1.0,
-                -0.75,  0.75, 0.0,    0.0, 1.0,
-            ], &[0, 1, 2, 0, 2, 3], &[(a_position, 3), (a_tex_coord, 2)]
-        );
+        let shader_program = GlShaderProgram::new(
+            &shaders, &attrib_bindings![a_position, a_tex_coord, a_layer]
+        ).unwrap();
         let uniforms = Uniforms::from_program(&shader_program);
+        let textures = GlTextureArray::from_files(&registry.images()).unwrap();
+
+        // Sheets of the wrong size would shift the layers of those after.
+        assert_eq!(
+            textures.layers(), registry.layer_count(),
+            "sprite sheet images do not match their rows"
+        );
         Self {
-            wall_texture: glwrap::texture_from_file(texture_creator, "textures/wall00.png"),
-            floor_texture: glwrap::texture_from_file(texture_creator, "textures/floor00.png"),
+            textures,
             shader_program,
             uniforms,
-            a_position, a_tex_coord,
-            wall_vertex_array, floor_vertex_array
+            a_position, a_tex_coord, a_layer,
+            level_mesh: LevelMesh::new(registry, [a_position, a_tex_coord, a_layer]),
+            sprite_batch: SpriteBatch::new(&[(a_position, 3), (a_tex_coord, 2), (a_layer, 1)])
         }
     }
 }
 
-new_key_type! {
-    struct ModelKey;
-    struct ShaderKey;
-    struct RenderElementKey;
-    struct TextureElementKey;
-    struct VertexArrayKey;
+/// An image of the texture registry, drawn as a billboard.
+#[derive(Copy, Clone)]
+pub struct Sprite {
+    /// Texture array layer, the same as the texture registry index.
+    pub layer: TextureIndex
 }
 
+/// A frame of a sprite sheet, drawn as a billboard showing the side of
+/// the element that faces the camera.
 #[derive(Copy, Clone)]
-struct Sprite {
-    texture: TextureElementKey,
-    shape: VertexArrayKey
+pub struct DirectionalSprite {
+    /// Layer of the frame as seen from the front, the other rotations
+    /// follow it, see `game::sprites`.
+    pub first_layer: TextureIndex
 }
 
 #[derive(Copy, Clone)]
-struct Tile {
+pub struct Tile {
 
 }
 
 #[derive(Copy, Clone)]
 pub enum Model {
     Sprite(Sprite),
+    DirectionalSprite(DirectionalSprite),
     Tile(Tile)
 }
 
+/// Something to draw this frame, other than the level itself.
 #[derive(Copy, Clone)]
-struct RenderElement {
-    model: ModelKey,
-    pos: [i32; 2],
-    facing: [i32; 2]
+pub struct RenderElement {
+    pub model: Model,
+    /// In cells, like the positions of the game.
+    pub pos: Vec3,
+    pub facing: Vec3
 }
 
+#[derive(Copy, Clone)]
 pub struct ViewSettings {
     pub pos: Vec3,
     pub facing: Vec3,
     pub height: f32
 }
 
-pub struct GlEngine<'r> {
-    world_graphics: DungeonGraphics<'r>,
-    models: SlotMap<ModelKey, Model>,
-    render_elements: SlotMap<RenderElementKey, RenderElement>
-}
+impl ViewSettings {
+    /// Blends two views, `alpha` going from 0 (`self`) to 1 (`other`).
+    pub fn lerp(&self, other: &ViewSettings, alpha: f32) -> Self {
+        let facing = glm::lerp(&self.facing, &other.facing, alpha);
 
-impl<'r> GlEngine<'r> {
-    pub fn new(texture_creator: & 'r TextureCreator<WindowContext>) -> Self {
         Self {
-            world_graphics: DungeonGraphics::new(&texture_creator),
-            models: SlotMap::with_capacity_and_key(16),
-            render_elements: SlotMap::with_capacity_and_key(16)
+            pos: glm::lerp(&self.pos, &other.pos, alpha),
+            // Opposite facings cancel out, keep the newest one then.
+            facing: if facing.norm() > 1e-3 { facing.normalize() } else { other.facing },
+            height: self.height + (other.height - self.height) * alpha
+        }
+    }
+}
+
+/// What went into drawing the frame so far.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    /// Cells of the level with quads in the static mesh.
+    pub cells: usize
+}
+
+pub struct GlEngine {
+    world_graphics: DungeonGraphics,
+    /// Draws the weapon of the player over the world.
+    overlay: Overlay,
+    text: TextRenderer,
+    /// Status bar, at the bottom of the screen below the view.
+    hud: HudGraphics,
+    automap: AutomapGraphics,
+    /// Size of the screen, in pixels.
+    screen: (f32, f32),
+    /// Vertical field of view, in radians.
+    field_of_view: f32,
+    stats: RenderStats
+}
+
+impl GlEngine {
+    /// * `screen`: size of the drawable area of the window, in pixels.
+    /// * `field_of_view`: vertical field of view, in radians.
+    pub fn new(registry: &TextureRegistry, screen: (f32, f32), field_of_view: f32) -> Self {
+        let mut engine = Self {
+            world_graphics: DungeonGraphics::new(registry),
+            overlay: Overlay::new(),
+            text: TextRenderer::from_file("textures/font.ron").unwrap(),
+            hud: HudGraphics::from_file("textures/hud.ron").unwrap(),
+            automap: AutomapGraphics::new(),
+            screen: (1.0, 1.0),
+            field_of_view,
+            stats: RenderStats::default()
+        };
+
+        engine.set_screen_size(screen.0, screen.1);
+        engine
+    }
+
+    /// Follows the size of the window.
+    /// * `width`, `height`: size of the drawable area of the window, in
+    ///   pixels.
+    pub fn set_screen_size(&mut self, width: f32, height: f32) {
+        self.screen = (width.max(1.0), height.max(1.0));
+        self.text.set_screen_size(self.screen.0, self.screen.1);
+    }
+
+    /// Draws text over the screen, see `TextRenderer::draw_text`.
+    pub fn draw_text(&mut self, pos: (f32, f32), scale: f32, color: [f32; 4], text: &str) {
+        self.stats.draw_calls += self.text.draw_text(pos, scale, color, text);
+    }
+
+    /// Draws the status bar at the bottom of the screen.
+    pub fn draw_hud(&mut self, status: &HudStatus) {
+        self.stats.draw_calls += self.hud.draw(&self.text, status, self.screen.0);
+    }
+
+    /// Draws the map of the cells seen over the view, when visible.
+    /// * `explored`: cells seen so far, by cell index.
+    /// * `pos`, `direction`: where the player stands and looks, in cells.
+    pub fn draw_automap(
+        &mut self, world: &DungeonFloor, explored: &[bool], pos: Vec3, direction: Vec3,
+        automap: &Automap
+    ) {
+        if !automap.visible {
+            return;
         }
+
+        let area = automap.area(self.view_area());
+        let geometry = build_automap_geometry(world, explored, pos, direction, automap, area);
+
+        self.stats.draw_calls += self.automap.draw(&geometry, area, self.screen.0, self.screen.1);
+    }
+
+    /// The part of the screen the world is drawn in, above the status bar.
+    pub fn view_area(&self) -> Area {
+        let (width, height) = self.screen;
+        let bar = self.hud.hud().bar_height(width).min(height - 1.0);
+
+        Area { x: 0.0, y: bar, width, height: height - bar }
+    }
+
+    /// Height of a line of text, see `Font::line_height`.
+    pub fn line_height(&self, scale: f32) -> f32 {
+        self.text.font().line_height(scale)
+    }
+
+    pub fn screen_size(&self) -> (f32, f32) {
+        self.screen
     }
 
-    pub fn render(&mut self, world: &DungeonFloor, view_settings: ViewSettings) {
-        self.render_world(world, &view_settings);
-/*
-        for (_, element) in self.render_elements.iter() {
+    /// Returns: what went into the frame since `render` started it.
+    pub fn stats(&self) -> RenderStats {
+        self.stats
+    }
+
+    /// Draws the level, then the render elements over it, then the weapon
+    /// of the player over everything, above the status bar.
+    /// * `elements`: what stands in the level this frame.
+    /// * `weapon`: the weapon of the player, if any.
+    pub fn render(
+        &mut self, world: &DungeonFloor, elements: &[RenderElement], view_settings: ViewSettings,
+        weapon: Option<&WeaponView>
+    ) {
+        let (width, height) = self.screen;
+        let view = self.view_area();
+        let view_height = view.height;
+
+        self.stats = RenderStats::default();
+        unsafe {
+            gl::Viewport(0, view.y as i32, width as i32, view_height as i32);
+        }
+        self.render_world(world, elements, &view_settings, width / view_height);
+
+        if let Some(weapon) = weapon {
+            self.overlay.update(&build_view_model_geometry(weapon, width, view_height));
+            self.stats.draw_calls += self.overlay.draw(
+                &self.world_graphics.textures, width, view_height
+            );
+        }
 
-        }*/
+        unsafe {
+            gl::Viewport(0, 0, width as i32, height as i32);
+        }
     }
 
-    fn render_world(&mut self, world: &DungeonFloor, view_settings: &ViewSettings) {
-        let _prg_bind = Bind::new(&self.world_graphics.shader_program);
+    /// * `aspect`: width of the view over its height.
+    fn render_world(
+        &mut self, world: &DungeonFloor, elements: &[RenderElement], view_settings: &ViewSettings,
+        aspect: f32
+    ) {
+        self.world_graphics.level_mesh.update(world);
+        self.world_graphics.sprite_batch.update(elements, view_settings);
+
+        let graphics = &self.world_graphics;
+        let _prg_bind = Bind::new(&graphics.shader_program);
         let projection_matrix: [[f32; 4]; 4] = glm::perspective(
-            8.0 / 6.0, 3.14 * 0.4, 0.1, 100.0
+            aspect, self.field_of_view, 0.1, 100.0
         ).into();
         let view_matrix: [[f32; 4]; 4] = glm::look_at(
             &Vec3::new(
@@ -167,45 +278,24 @@ impl<'r> GlEngine<'r> {
             &Vec3::new(0.0, 0.0, 1.0)).into();
 
         unsafe {
-            gl::Uniform2d(self.world_graphics.uniforms.u_resolution, 800.0, 600.0);
-            gl::UniformMatrix4fv(self.world_graphics.uniforms.u_projection_matrix, 1, gl::FALSE,
+            gl::Uniform2f(graphics.uniforms.u_resolution, self.screen.0, self.screen.1);
+            gl::UniformMatrix4fv(graphics.uniforms.u_projection_matrix, 1, gl::FALSE,
                 &projection_matrix[0][0] as *const f32);
-            gl::UniformMatrix4fv(self.world_graphics.uniforms.u_view_matrix, 1, gl::FALSE,
+            gl::UniformMatrix4fv(graphics.uniforms.u_view_matrix, 1, gl::FALSE,
                 &view_matrix[0][0] as *const f32);
-            gl::Uniform1ui(self.world_graphics.uniforms.u_texture0, gl::TEXTURE0);
+            gl::Uniform1i(graphics.uniforms.u_texture0, 0);
 
             gl::ActiveTexture(gl::TEXTURE0);
         }
 
-        let w = world.width;
-        for x in 0..w {
-            for y in 0..world.height {
-                let model_matrix: [[f32; 4]; 4] = glm::translation(
-                    &Vec3::new(x as f32 * 1.5, y as f32 * 1.5, 0.0)
-                ).into();
-                unsafe { gl::UniformMatrix4fv(self.world_graphics.uniforms.u_model_matrix, 1,
-                    gl::FALSE, &model_matrix[0][0] as *const f32); }
-
-                match world.cells[x + y * w] {
-                    Floor => {
-                        let _vertex_bind = Bind::new(&self.world_graphics.floor_vertex_array);
-                        unsafe {
-                            self.world_graphics.floor_texture.gl_bind_texture();
-                            gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, 0 as *const std::ffi::c_void);
-                            self.world_graphics.floor_texture.gl_unbind_texture();
-                        }
-                    }
-                    Wall => {
-                        let _vertex_bind = Bind::new(&self.world_graphics.wall_vertex_array);
-                        unsafe {
-                            self.world_graphics.wall_texture.gl_bind_texture();
-                            gl::DrawElements(gl::TRIANGLES, 24, gl::UNSIGNED_INT, 0 as *const std::ffi::c_void);
-                            self.world_graphics.wall_texture.gl_unbind_texture();
-                        }
-                    }
-                    _ => { }
-                }
-            }
-        }
+        let model_matrix: [[f32; 4]; 4] = glm::identity::<f32, glm::U4>().into();
+        unsafe { gl::UniformMatrix4fv(graphics.uniforms.u_model_matrix, 1,
+            gl::FALSE, &model_matrix[0][0] as *const f32); }
+
+        let _texture_bind = Bind::new(&graphics.textures);
+        self.stats.cells = graphics.level_mesh.cells();
+        self.stats.draw_calls += graphics.level_mesh.draw();
+        // Sprites are alpha tested against the depth of the level.
+        self.stats.draw_calls += graphics.sprite_batch.draw();
     }
 }
@@ -0,0 +1,542 @@
+//! The status bar at the bottom of the screen: health, armor, ammo, keys
+//! and the face of the player.
+//!
+//! Its images come from an atlas cut in a grid of cells, like the fonts of
+//! `text`, and a RON file lays them out along with the numbers, so that the
+//! bar can be rearranged without touching the code. Positions are in pixels
+//! of a bar `width` by `height`, from its bottom left corner, and the whole
+//! bar stretches to the width of the screen.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use serde::Deserialize;
+use glm::Vec3;
+use crate::game::{
+    components::Key,
+    model::PLAYER_HEALTH
+};
+use crate::rendering::{
+    glwrap::GlTexture,
+    level_mesh::LevelGeometry,
+    text::{ Font, TextRenderer }
+};
+
+/// Seconds the face winces after the player gets hurt.
+pub const FACE_HURT_TIME: f32 = 0.6;
+/// Seconds the face looks each way while nothing happens.
+pub const GLANCE_TIME: f32 = 1.2;
+/// Where the face looks in turn while nothing happens.
+const GLANCES: [Look; 4] = [Look::Ahead, Look::Left, Look::Ahead, Look::Right];
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// On-disk layout of a status bar.
+#[derive(Deserialize)]
+struct HudRon {
+    image: String,
+    cell_width: usize,
+    cell_height: usize,
+    columns: usize,
+    rows: usize,
+    width: f32,
+    height: f32,
+    /// Drawn first, in order.
+    images: Vec<ImageRon>,
+    labels: Vec<LabelRon>,
+    numbers: Vec<NumberRon>,
+    face: FaceRon,
+    /// Shown while the player holds their key.
+    keys: Vec<KeySlotRon>
+}
+
+/// A cell of the atlas stretched over a rectangle.
+#[derive(Clone, Debug, Deserialize)]
+struct ImageRon {
+    cell: usize,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32
+}
+
+/// Text centered on `x`, with its bottom at `y`.
+#[derive(Clone, Debug, Deserialize)]
+struct LabelRon {
+    text: String,
+    x: f32,
+    y: f32,
+    /// Bar pixels per font pixel.
+    scale: f32,
+    color: [f32; 4]
+}
+
+/// A value of the player, placed like a label.
+#[derive(Clone, Debug, Deserialize)]
+struct NumberRon {
+    value: Stat,
+    x: f32,
+    y: f32,
+    scale: f32,
+    color: [f32; 4]
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+enum Stat {
+    Health,
+    Armor,
+    Ammo
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct FaceRon {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    /// Faces from full health down to the brink of death, each bracket the
+    /// same share of the health.
+    moods: Vec<MoodRon>,
+    dead: usize
+}
+
+/// Cells of the faces of a health bracket.
+#[derive(Copy, Clone, Debug, Deserialize)]
+struct MoodRon {
+    ahead: usize,
+    left: usize,
+    right: usize,
+    hurt: usize
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct KeySlotRon {
+    key: Key,
+    cell: usize,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32
+}
+
+/// Where the face looks.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Look {
+    Ahead,
+    Left,
+    Right,
+    /// Winces, after the player got hurt.
+    Hurt
+}
+
+/// What the status bar shows this frame.
+#[derive(Clone, Debug)]
+pub struct HudStatus {
+    pub health: i32,
+    pub armor: i32,
+    /// Rounds of the weapon in hand, `None` for weapons that never run out.
+    pub ammo: Option<i32>,
+    pub keys: BTreeSet<Key>,
+    pub look: Look
+}
+
+/// Text of the status bar, for a `TextRenderer` to draw.
+#[derive(Clone, Debug)]
+pub struct HudText {
+    /// Bottom left corner, in pixels from the bottom left corner of the
+    /// screen.
+    pub pos: (f32, f32),
+    pub scale: f32,
+    pub color: [f32; 4],
+    pub text: String
+}
+
+/// What to draw of the status bar, pass by pass.
+pub struct HudFrame {
+    /// Images of the bar, drawn first: the background and panels are
+    /// opaque, and would hide anything drawn before them.
+    pub underlay: LevelGeometry,
+    /// Labels and numbers, drawn over the underlay.
+    pub texts: Vec<HudText>
+}
+
+/// Makes the face glance around, and wince when the player gets hurt.
+#[derive(Default)]
+pub struct FacePortrait {
+    /// Health at the previous update.
+    health: Option<i32>,
+    /// Seconds left wincing.
+    hurt: f32,
+    /// Seconds since the face started glancing around.
+    time: f32
+}
+
+impl FacePortrait {
+    /// * `health`: health of the player.
+    /// * `dt`: seconds since the previous frame.
+    pub fn update(&mut self, health: i32, dt: f32) {
+        self.hurt = (self.hurt - dt).max(0.0);
+        self.time += dt;
+
+        if let Some(previous) = self.health {
+            if health < previous {
+                self.hurt = FACE_HURT_TIME;
+            }
+        }
+
+        self.health = Some(health);
+    }
+
+    pub fn look(&self) -> Look {
+        if self.hurt > 0.0 {
+            return Look::Hurt;
+        }
+
+        GLANCES[(self.time / GLANCE_TIME) as usize % GLANCES.len()]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Hud {
+    pub image: String,
+    /// Size of the cells of the atlas, in pixels.
+    pub cell_width: usize,
+    pub cell_height: usize,
+    pub columns: usize,
+    pub rows: usize,
+    /// Size of the bar the layout places things in, in pixels.
+    pub width: f32,
+    pub height: f32,
+    images: Vec<ImageRon>,
+    labels: Vec<LabelRon>,
+    numbers: Vec<NumberRon>,
+    face: FaceRon,
+    keys: Vec<KeySlotRon>
+}
+
+impl Hud {
+    /// Loads a status bar layout from a RON file.
+    /// Returns: `Result<Hud, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.as_ref().display(), error));
+            }
+        };
+
+        Self::parse(&source).map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+    }
+
+    /// Parses and validates a status bar layout from RON source.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let hud: HudRon = ron::de::from_str(source)
+            .map_err(|e| format!("{}", e))?;
+
+        if hud.cell_width == 0 || hud.cell_height == 0 {
+            return Err(format!("invalid cell size {}x{}", hud.cell_width, hud.cell_height));
+        }
+
+        if hud.width <= 0.0 || hud.height <= 0.0 {
+            return Err(format!("invalid bar size {}x{}", hud.width, hud.height));
+        }
+
+        if hud.face.moods.is_empty() {
+            return Err("the face has no moods".to_string());
+        }
+
+        let cells = hud.images.iter().map(|image| image.cell)
+            .chain(hud.keys.iter().map(|slot| slot.cell))
+            .chain(hud.face.moods.iter().flat_map(|mood| {
+                vec![mood.ahead, mood.left, mood.right, mood.hurt]
+            }))
+            .chain(Some(hud.face.dead));
+
+        for cell in cells {
+            if cell >= hud.columns * hud.rows {
+                return Err(format!(
+                    "cell {} falls outside the {}x{} atlas", cell, hud.columns, hud.rows
+                ));
+            }
+        }
+
+        Ok(Self {
+            image: hud.image,
+            cell_width: hud.cell_width,
+            cell_height: hud.cell_height,
+            columns: hud.columns,
+            rows: hud.rows,
+            width: hud.width,
+            height: hud.height,
+            images: hud.images,
+            labels: hud.labels,
+            numbers: hud.numbers,
+            face: hud.face,
+            keys: hud.keys
+        })
+    }
+
+    /// Height of the bar on a screen, in pixels.
+    /// * `screen_width`: width of the screen in pixels.
+    pub fn bar_height(&self, screen_width: f32) -> f32 {
+        self.height * screen_width / self.width
+    }
+
+    /// Cell of the face that shows how the player is doing.
+    pub fn face_cell(&self, status: &HudStatus) -> usize {
+        if status.health <= 0 {
+            return self.face.dead;
+        }
+
+        let moods = self.face.moods.len() as i32;
+        let bracket = ((PLAYER_HEALTH - status.health) * moods / PLAYER_HEALTH).clamp(0, moods - 1);
+        let mood = self.face.moods[bracket as usize];
+
+        match status.look {
+            Look::Ahead => mood.ahead,
+            Look::Left => mood.left,
+            Look::Right => mood.right,
+            Look::Hurt => mood.hurt
+        }
+    }
+
+    /// Builds the images of the bar, and the text to write over them.
+    /// * `font`: font the text will be drawn with, to center it.
+    /// * `screen_width`: width of the screen in pixels.
+    /// Returns: the passes to draw, see `HudFrame`.
+    pub fn build(&self, status: &HudStatus, font: &Font, screen_width: f32) -> HudFrame {
+        let scale = screen_width / self.width;
+        let mut geometry = LevelGeometry::default();
+        let face = &self.face;
+        let keys = self.keys.iter()
+            .filter(|slot| status.keys.contains(&slot.key))
+            .map(|slot| (slot.cell, slot.x, slot.y, slot.width, slot.height));
+
+        for (cell, x, y, width, height) in self.images.iter()
+            .map(|image| (image.cell, image.x, image.y, image.width, image.height))
+            .chain(Some((self.face_cell(status), face.x, face.y, face.width, face.height)))
+            .chain(keys) {
+            let (x, y, width, height) = (x * scale, y * scale, width * scale, height * scale);
+
+            geometry.push_quad(
+                [
+                    Vec3::new(x, y, 0.0),
+                    Vec3::new(x + width, y, 0.0),
+                    Vec3::new(x + width, y + height, 0.0),
+                    Vec3::new(x, y + height, 0.0)
+                ],
+                self.cell_uvs(cell),
+                0
+            );
+        }
+
+        let labels = self.labels.iter()
+            .map(|label| (label.text.clone(), label.x, label.y, label.scale, label.color));
+        let numbers = self.numbers.iter()
+            .map(|number| {
+                let text = match (number.value, status.ammo) {
+                    (Stat::Health, _) => status.health.to_string(),
+                    (Stat::Armor, _) => status.armor.to_string(),
+                    (Stat::Ammo, Some(ammo)) => ammo.to_string(),
+                    (Stat::Ammo, None) => "--".to_string()
+                };
+
+                (text, number.x, number.y, number.scale, number.color)
+            });
+        let texts = labels.chain(numbers)
+            .map(|(text, x, y, text_scale, color)| {
+                let text_scale = text_scale * scale;
+                let (width, _) = font.measure(text_scale, &text);
+
+                HudText {
+                    pos: (x * scale - width / 2.0, y * scale),
+                    scale: text_scale,
+                    color,
+                    text
+                }
+            })
+            .collect();
+
+        HudFrame { underlay: geometry, texts }
+    }
+
+    /// Corners of a cell in the atlas, in the order of `push_quad`.
+    fn cell_uvs(&self, cell: usize) -> [[f32; 2]; 4] {
+        let left = (cell % self.columns) as f32 / self.columns as f32;
+        let right = left + 1.0 / self.columns as f32;
+        let top = (cell / self.columns) as f32 / self.rows as f32;
+        let bottom = top + 1.0 / self.rows as f32;
+
+        [[left, bottom], [right, bottom], [right, top], [left, top]]
+    }
+}
+
+/// A status bar and its atlas.
+pub struct HudGraphics {
+    hud: Hud,
+    texture: GlTexture
+}
+
+impl HudGraphics {
+    /// Loads a status bar layout and its atlas.
+    /// * `path`: RON layout file, usually `textures/hud.ron`.
+    /// Returns: `Result<HudGraphics, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let hud = Hud::from_file(&path)?;
+        let texture = GlTexture::from_file(&hud.image)?;
+
+        if texture.width() != hud.columns * hud.cell_width
+            || texture.height() != hud.rows * hud.cell_height {
+            return Err(format!(
+                "{}: expected a {}x{} atlas, found {}x{}",
+                hud.image, hud.columns * hud.cell_width, hud.rows * hud.cell_height,
+                texture.width(), texture.height()
+            ));
+        }
+
+        Ok(Self { hud, texture })
+    }
+
+    pub fn hud(&self) -> &Hud {
+        &self.hud
+    }
+
+    /// Draws the bar at the bottom of the screen, over whatever it holds.
+    /// * `text`: draws the bar, and the text over it.
+    /// * `screen_width`: width of the screen in pixels.
+    /// Returns: the draw calls issued.
+    pub fn draw(&self, text: &TextRenderer, status: &HudStatus, screen_width: f32) -> usize {
+        let frame = self.hud.build(status, text.font(), screen_width);
+        let mut calls = text.draw_quads(&self.texture, &frame.underlay, WHITE);
+
+        for line in frame.texts.iter() {
+            calls += text.draw_text(line.pos, line.scale, line.color, &line.text);
+        }
+
+        calls
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::level_mesh::FLOATS_PER_VERTEX;
+
+    const FONT: &str = r#"FontRon(
+        image: "font.png",
+        cell_width: 16,
+        cell_height: 16,
+        columns: 16,
+        rows: 16,
+        spacing: 0,
+        glyphs: {
+            '1': (left: 0, width: 8),
+            '5': (left: 0, width: 8),
+            '0': (left: 0, width: 8),
+            'H': (left: 0, width: 8),
+        },
+    )"#;
+
+    const HUD: &str = r#"HudRon(
+        image: "hud.png",
+        cell_width: 32,
+        cell_height: 32,
+        columns: 8,
+        rows: 8,
+        width: 400.0,
+        height: 50.0,
+        images: [
+            (cell: 0, x: 0.0, y: 0.0, width: 400.0, height: 50.0),
+        ],
+        labels: [
+            (text: "H", x: 100.0, y: 30.0, scale: 1.0, color: (1.0, 1.0, 1.0, 1.0)),
+        ],
+        numbers: [
+            (value: Health, x: 100.0, y: 5.0, scale: 2.0, color: (1.0, 0.0, 0.0, 1.0)),
+        ],
+        face: (
+            x: 180.0, y: 5.0, width: 40.0, height: 40.0,
+            moods: [
+                (ahead: 8, left: 9, right: 10, hurt: 11),
+                (ahead: 12, left: 13, right: 14, hurt: 15),
+            ],
+            dead: 16,
+        ),
+        keys: [
+            (key: Gold, cell: 2, x: 300.0, y: 10.0, width: 30.0, height: 30.0),
+            (key: Silver, cell: 3, x: 340.0, y: 10.0, width: 30.0, height: 30.0),
+        ],
+    )"#;
+
+    fn status(health: i32, look: Look) -> HudStatus {
+        HudStatus {
+            health,
+            armor: 0,
+            ammo: None,
+            keys: BTreeSet::new(),
+            look
+        }
+    }
+
+    #[test]
+    fn stretches_the_bar_to_the_screen() {
+        let hud = Hud::parse(HUD).unwrap();
+        let font = Font::parse(FONT).unwrap();
+        let mut full = status(150, Look::Ahead);
+
+        full.keys.insert(Key::Silver);
+
+        let HudFrame { underlay: geometry, texts } = hud.build(&full, &font, 800.0);
+
+        assert_eq!(hud.bar_height(800.0), 100.0);
+        // Background, face and the one key held.
+        assert_eq!(geometry.quad_count(), 3);
+        assert_eq!(&geometry.vertices[2 * 4 * FLOATS_PER_VERTEX..][..2], &[680.0, 20.0]);
+
+        let number = &texts[1];
+        assert_eq!(number.text, "150");
+        assert_eq!(number.scale, 4.0);
+        // Three glyphs of 8 pixels, 4 times as large, centered on 200.
+        assert_eq!(number.pos, (200.0 - 48.0, 10.0));
+        assert_eq!(texts[0].pos, (200.0 - 8.0, 60.0));
+    }
+
+    #[test]
+    fn shows_how_the_player_is_doing() {
+        let hud = Hud::parse(HUD).unwrap();
+
+        assert_eq!(hud.face_cell(&status(PLAYER_HEALTH, Look::Ahead)), 8);
+        assert_eq!(hud.face_cell(&status(PLAYER_HEALTH / 2 + 1, Look::Left)), 9);
+        assert_eq!(hud.face_cell(&status(PLAYER_HEALTH / 2, Look::Right)), 14);
+        assert_eq!(hud.face_cell(&status(1, Look::Hurt)), 15);
+        assert_eq!(hud.face_cell(&status(0, Look::Ahead)), 16);
+    }
+
+    #[test]
+    fn winces_then_glances_around() {
+        let mut face = FacePortrait::default();
+
+        face.update(100, 0.1);
+        assert_eq!(face.look(), Look::Ahead);
+
+        face.update(90, 0.1);
+        assert_eq!(face.look(), Look::Hurt);
+
+        face.update(90, FACE_HURT_TIME);
+        assert_ne!(face.look(), Look::Hurt);
+
+        let looks: Vec<_> = (0..4)
+            .map(|_| {
+                face.update(90, GLANCE_TIME);
+                face.look()
+            })
+            .collect();
+
+        assert!(looks.contains(&Look::Left) && looks.contains(&Look::Right));
+    }
+
+    #[test]
+    fn rejects_cells_outside_the_atlas() {
+        let layout = HUD.replace("dead: 16", "dead: 64");
+
+        assert!(Hud::parse(&layout).unwrap_err().contains("cell 64"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Puffs shown for a moment where shots land, drawn as sprites.
+
+use glm::Vec3;
+use crate::game::model::TextureIndex;
+use crate::rendering::{
+    graphics::{ Model, RenderElement, Sprite, ViewSettings },
+    level_mesh::CELL_SIZE,
+    sprites::SPRITE_SIZE
+};
+
+/// Seconds a puff stays on screen.
+pub const IMPACT_TIME: f32 = 0.2;
+/// How far puffs show in front of what was hit, in cells, so that walls do
+/// not swallow them.
+const STAND_OFF: f32 = 0.05;
+
+struct Puff {
+    pos: Vec3,
+    /// Seconds since the shot landed.
+    age: f32
+}
+
+pub struct ImpactEffects {
+    layer: Option<TextureIndex>,
+    puffs: Vec<Puff>
+}
+
+impl ImpactEffects {
+    /// * `layer`: texture of the puffs, nothing shows without one.
+    pub fn new(layer: Option<TextureIndex>) -> Self {
+        Self {
+            layer,
+            puffs: Vec::new()
+        }
+    }
+
+    /// Shows a puff where a shot landed.
+    /// * `point`: where the shot landed, on the floor plane.
+    /// * `view`: the eyes the shot was fired from.
+    pub fn spawn(&mut self, point: Vec3, view: &ViewSettings) {
+        let back = Vec3::new(view.pos.x - point.x, view.pos.y - point.y, 0.0);
+        let back = if back.norm() > STAND_OFF { back.normalize() * STAND_OFF } else { back };
+        // Sprites stand on their position, lift the middle of the puff to
+        // the height of the eyes.
+        let lift = (view.height - SPRITE_SIZE / 2.0) / CELL_SIZE;
+
+        self.puffs.push(Puff {
+            pos: Vec3::new(point.x + back.x, point.y + back.y, lift),
+            age: 0.0
+        });
+    }
+
+    /// Ages the puffs, and drops those that faded.
+    /// * `dt`: seconds since the previous frame.
+    pub fn update(&mut self, dt: f32) {
+        for puff in self.puffs.iter_mut() {
+            puff.age += dt;
+        }
+
+        self.puffs.retain(|puff| puff.age < IMPACT_TIME);
+    }
+
+    pub fn render_elements(&self) -> Vec<RenderElement> {
+        let layer = match self.layer {
+            Some(layer) => layer,
+            None => return Vec::new()
+        };
+
+        self.puffs.iter()
+            .map(|puff| RenderElement {
+                model: Model::Sprite(Sprite { layer }),
+                pos: puff.pos,
+                facing: Vec3::new(1.0, 0.0, 0.0)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stands_off_and_fades() {
+        let view = ViewSettings {
+            pos: Vec3::new(1.0, 8.0, 0.0),
+            facing: Vec3::new(1.0, 0.0, 0.0),
+            height: 1.77
+        };
+        let mut effects = ImpactEffects::new(Some(4));
+
+        effects.spawn(Vec3::new(5.5, 8.0, 0.0), &view);
+
+        let elements = effects.render_elements();
+        assert_eq!(elements.len(), 1);
+        assert!(elements[0].pos.x < 5.5);
+        assert!((elements[0].pos.z * CELL_SIZE + SPRITE_SIZE / 2.0 - 1.77).abs() < 1e-4);
+
+        effects.update(IMPACT_TIME / 2.0);
+        assert_eq!(effects.render_elements().len(), 1);
+        effects.update(IMPACT_TIME);
+        assert!(effects.render_elements().is_empty());
+        assert!(ImpactEffects::new(None).render_elements().is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+//! Bakes a `DungeonFloor` into a handful of vertex arrays, so that the
+//! whole level draws in a couple of calls instead of one or more per cell.
+//!
+//! Vertices are laid out as position (3), texture coordinates (2) and
+//! texture array layer (1), in world space.
+
+use glm::{ Vec3, Vec4 };
+use gl::types::{ GLfloat, GLuint };
+use crate::rendering::glwrap::{ GlVertexArray, Bind };
+use crate::game::{
+    doors,
+    model::{
+        DungeonFloor, TextureIndex,
+        DungeonCell::{ Empty, Wall, Floor, Door, OpenDoor }
+    },
+    textures::TextureRegistry
+};
+
+/// Size of a cell in world units.
+pub const CELL_SIZE: f32 = 1.5;
+/// Height of walls and ceilings in world units.
+pub const WALL_HEIGHT: f32 = 3.0;
+
+const HALF: f32 = CELL_SIZE / 2.0;
+pub const FLOATS_PER_VERTEX: usize = 6;
+
+/// CPU side vertex and index buffers.
+#[derive(Default)]
+pub struct LevelGeometry {
+    pub vertices: Vec<GLfloat>,
+    pub indices: Vec<GLuint>
+}
+
+impl LevelGeometry {
+    pub fn quad_count(&self) -> usize {
+        self.indices.len() / 6
+    }
+
+    /// Returns: the draw calls a `Mesh` of the geometry issues, none when
+    /// there is nothing to draw.
+    pub fn draw_calls(&self) -> usize {
+        if self.indices.is_empty() { 0 } else { 1 }
+    }
+
+    /// Appends a quad wound counter-clockwise as seen from its front.
+    pub fn push_quad(&mut self, corners: [Vec3; 4], uvs: [[f32; 2]; 4], texture: TextureIndex) {
+        let base = (self.vertices.len() / FLOATS_PER_VERTEX) as GLuint;
+
+        for (corner, uv) in corners.iter().zip(uvs.iter()) {
+            self.vertices.extend_from_slice(&[
+                corner.x, corner.y, corner.z, uv[0], uv[1], texture as f32
+            ]);
+        }
+
+        self.indices.extend_from_slice(&[
+            base, base + 1, base + 2,
+            base, base + 2, base + 3
+        ]);
+    }
+}
+
+const WALL_UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 2.0], [0.0, 2.0]];
+const FLOOR_UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+const CEILING_UVS: [[f32; 2]; 4] = [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]];
+
+/// Builds the static part of the level: walls, floors and ceilings.
+pub fn build_level_geometry(world: &DungeonFloor, registry: &TextureRegistry) -> LevelGeometry {
+    let mut geometry = LevelGeometry::default();
+    let w = world.width;
+
+    for y in 0..world.height {
+        for x in 0..w {
+            let center = Vec3::new(x as f32 * CELL_SIZE, y as f32 * CELL_SIZE, 0.0);
+            let cell = world.cells[x + y * w];
+            let overrides = world.textures.get(&(x + y * w));
+            let floor_texture = match cell {
+                Wall(texture) => {
+                    push_wall(&mut geometry, world, x as i32, y as i32, texture);
+                    continue;
+                }
+                Empty => continue,
+                Floor(texture) => texture,
+                Door | OpenDoor => registry.floor
+            };
+
+            geometry.push_quad(
+                [
+                    center + Vec3::new(-HALF, -HALF, 0.0),
+                    center + Vec3::new(HALF, -HALF, 0.0),
+                    center + Vec3::new(HALF, HALF, 0.0),
+                    center + Vec3::new(-HALF, HALF, 0.0)
+                ],
+                FLOOR_UVS,
+                overrides.and_then(|t| t.floor).unwrap_or(floor_texture)
+            );
+            // Wound the other way round, to face down.
+            geometry.push_quad(
+                [
+                    center + Vec3::new(-HALF, -HALF, WALL_HEIGHT),
+                    center + Vec3::new(-HALF, HALF, WALL_HEIGHT),
+                    center + Vec3::new(HALF, HALF, WALL_HEIGHT),
+                    center + Vec3::new(HALF, -HALF, WALL_HEIGHT)
+                ],
+                CEILING_UVS,
+                overrides.and_then(|t| t.ceiling).unwrap_or(registry.ceiling)
+            );
+        }
+    }
+
+    geometry
+}
+
+/// Counts the cells that add quads to the static mesh: walkable cells, and
+/// walls with a face in sight.
+pub fn drawn_cells(world: &DungeonFloor) -> usize {
+    (0..world.height as i32)
+        .flat_map(|y| (0..world.width as i32).map(move |x| (x, y)))
+        .filter(|&(x, y)| match world.cell(x, y) {
+            Some(Wall(_)) => [(0, -1), (1, 0), (0, 1), (-1, 0)].iter().any(|(dx, dy)| {
+                matches!(world.cell(x + dx, y + dy), Some(Floor(_)) | Some(Door) | Some(OpenDoor))
+            }),
+            Some(Empty) | None => false,
+            Some(_) => true
+        })
+        .count()
+}
+
+/// Builds the door panels at their current offset.
+pub fn build_door_geometry(world: &DungeonFloor, registry: &TextureRegistry) -> LevelGeometry {
+    let mut geometry = LevelGeometry::default();
+    let w = world.width;
+
+    for (index, cell) in world.cells.iter().enumerate() {
+        if *cell != Door && *cell != OpenDoor {
+            continue;
+        }
+
+        let (x, y) = (index % w, index / w);
+        let angle = if doors::spans_x(world, x as i32, y as i32) {
+            std::f32::consts::FRAC_PI_2
+        } else {
+            0.0
+        };
+        let offset = doors::progress(world, index) * CELL_SIZE;
+        // A thin two-sided panel spanning the cell along y, rotated to span
+        // x if needed and slid along its span to open.
+        let transform = glm::translation(
+            &Vec3::new(x as f32 * CELL_SIZE, y as f32 * CELL_SIZE, 0.0)
+        ) * glm::rotation(angle, &Vec3::new(0.0, 0.0, 1.0))
+            * glm::translation(&Vec3::new(0.0, offset, 0.0));
+        let place = |x: f32, y: f32, z: f32| -> Vec3 {
+            (transform * Vec4::new(x, y, z, 1.0)).xyz()
+        };
+
+        geometry.push_quad(
+            [
+                place(0.1, -HALF, 0.0),
+                place(0.1, HALF, 0.0),
+                place(0.1, HALF, WALL_HEIGHT),
+                place(0.1, -HALF, WALL_HEIGHT)
+            ],
+            WALL_UVS, registry.door
+        );
+        geometry.push_quad(
+            [
+                place(-0.1, HALF, 0.0),
+                place(-0.1, -HALF, 0.0),
+                place(-0.1, -HALF, WALL_HEIGHT),
+                place(-0.1, HALF, WALL_HEIGHT)
+            ],
+            WALL_UVS, registry.door
+        );
+    }
+
+    geometry
+}
+
+/// Emits the faces of a wall cell that can be seen, that is the ones
+/// bordering a cell the player can stand in or look through.
+fn push_wall(geometry: &mut LevelGeometry, world: &DungeonFloor, x: i32, y: i32,
+    texture: TextureIndex) {
+    let center = Vec3::new(x as f32 * CELL_SIZE, y as f32 * CELL_SIZE, 0.0);
+    let corners = [
+        Vec3::new(-HALF, -HALF, 0.0),
+        Vec3::new(HALF, -HALF, 0.0),
+        Vec3::new(HALF, HALF, 0.0),
+        Vec3::new(-HALF, HALF, 0.0)
+    ];
+    // The neighbour each face looks at, in the same order.
+    let neighbours = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+    // Walk around the cell counter-clockwise, one face per side.
+    for side in 0..4 {
+        let (dx, dy) = neighbours[side];
+
+        match world.cell(x + dx, y + dy) {
+            Some(Floor(_)) | Some(Door) | Some(OpenDoor) => { }
+            _ => continue
+        }
+
+        let a = center + corners[side];
+        let b = center + corners[(side + 1) % 4];
+        let up = Vec3::new(0.0, 0.0, WALL_HEIGHT);
+
+        geometry.push_quad([a, b, b + up, a + up], WALL_UVS, texture);
+    }
+}
+
+/// A `LevelGeometry` uploaded to the GPU.
+pub struct Mesh {
+    vertex_array: GlVertexArray,
+    index_count: i32,
+    draw_calls: usize
+}
+
+impl Mesh {
+    pub fn new(geometry: &LevelGeometry, attributes: &[(GLuint, usize)]) -> Self {
+        Self {
+            vertex_array: GlVertexArray::from_vertex_buffer(
+                &geometry.vertices, &geometry.indices, attributes
+            ),
+            index_count: geometry.indices.len() as i32,
+            draw_calls: geometry.draw_calls()
+        }
+    }
+
+    /// Returns: the draw calls issued.
+    pub fn draw(&self) -> usize {
+        if self.draw_calls == 0 {
+            return 0;
+        }
+
+        let _vertex_bind = Bind::new(&self.vertex_array);
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT,
+                0 as *const std::ffi::c_void);
+        }
+
+        self.draw_calls
+    }
+}
+
+/// Level meshes, rebuilt only when the floor changes.
+pub struct LevelMesh {
+    attributes: Vec<(GLuint, usize)>,
+    registry: TextureRegistry,
+    /// Revision of the floor the static mesh was built from.
+    revision: Option<u64>,
+    static_mesh: Option<Mesh>,
+    /// Revisions of the floor and of its doors the door mesh was built
+    /// from.
+    door_revision: Option<(u64, u64)>,
+    door_mesh: Option<Mesh>,
+    /// Cells the static mesh draws, see `drawn_cells`.
+    cells: usize
+}
+
+impl LevelMesh {
+    /// * `attributes`: position, texture coordinates and layer attributes.
+    pub fn new(registry: &TextureRegistry, attributes: [GLuint; 3]) -> Self {
+        Self {
+            attributes: vec![(attributes[0], 3), (attributes[1], 2), (attributes[2], 1)],
+            registry: registry.clone(),
+            revision: None,
+            static_mesh: None,
+            door_revision: None,
+            door_mesh: None,
+            cells: 0
+        }
+    }
+
+    /// Rebuilds whatever changed since the last call.
+    pub fn update(&mut self, world: &DungeonFloor) {
+        if self.revision != Some(world.revision) {
+            let geometry = build_level_geometry(world, &self.registry);
+            self.static_mesh = Some(Mesh::new(&geometry, &self.attributes));
+            self.revision = Some(world.revision);
+            self.cells = drawn_cells(world);
+        }
+
+        let door_revision = (world.revision, world.door_revision);
+
+        if self.door_revision != Some(door_revision) {
+            let geometry = build_door_geometry(world, &self.registry);
+            self.door_mesh = Some(Mesh::new(&geometry, &self.attributes));
+            self.door_revision = Some(door_revision);
+        }
+    }
+
+    /// Returns: the draw calls issued.
+    pub fn draw(&self) -> usize {
+        self.static_mesh.iter().chain(self.door_mesh.iter())
+            .map(|mesh| mesh.draw())
+            .sum()
+    }
+
+    pub fn cells(&self) -> usize {
+        self.cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uglythings::{ build_experimental_game, generated_map, per_cell_draw_calls };
+
+    fn registry() -> TextureRegistry {
+        TextureRegistry {
+            textures: vec!["wall".to_string(), "floor".to_string(), "ceiling".to_string()],
+            floor: 1,
+            ceiling: 2,
+            door: 0,
+            impact: None,
+            sheets: Vec::new(),
+            view_model: None
+        }
+    }
+
+    /// Counts the wall faces in a geometry: the only vertical quads.
+    fn wall_faces(geometry: &LevelGeometry) -> Vec<[Vec3; 4]> {
+        geometry.vertices
+            .chunks(FLOATS_PER_VERTEX * 4)
+            .map(|quad| {
+                let corner = |i: usize| Vec3::new(
+                    quad[i * FLOATS_PER_VERTEX],
+                    quad[i * FLOATS_PER_VERTEX + 1],
+                    quad[i * FLOATS_PER_VERTEX + 2]
+                );
+                [corner(0), corner(1), corner(2), corner(3)]
+            })
+            .filter(|corners| corners[0].z != corners[2].z)
+            .collect()
+    }
+
+    #[test]
+    fn bakes_floors_and_ceilings() {
+        let world = generated_map(16);
+        let walls = world.cells.iter().filter(|c| matches!(c, Wall(_))).count();
+        let walkable = world.cells.len() - walls;
+        let geometry = build_level_geometry(&world, &registry());
+        let faces = wall_faces(&geometry).len();
+
+        assert_eq!(geometry.quad_count(), faces + walkable * 2);
+        assert_eq!(geometry.vertices.len(), geometry.quad_count() * 4 * FLOATS_PER_VERTEX);
+    }
+
+    #[test]
+    fn culls_interior_wall_faces() {
+        let world = build_experimental_game().current_map;
+        let walls = world.cells.iter().filter(|c| matches!(c, Wall(_))).count();
+        let faces = wall_faces(&build_level_geometry(&world, &registry()));
+
+        // Every wall/floor border of the map, counted by hand.
+        assert_eq!(faces.len(), 36);
+        assert!(faces.len() < walls * 4);
+
+        // The cell just in front of each face must be walkable.
+        for face in faces.iter() {
+            let middle = (face[0] + face[1]) / 2.0;
+            let normal = (face[1] - face[0]).cross(&(face[3] - face[0])).normalize();
+            let probe = (middle + normal * HALF) / CELL_SIZE;
+            let cell = world.cell(probe.x.round() as i32, probe.y.round() as i32);
+
+            assert_eq!(cell, Some(Floor(1)), "face {:?} looks at {:?}", face, cell);
+        }
+    }
+
+    #[test]
+    fn counts_the_cells_drawn() {
+        let world = DungeonFloor::new(3, 3, vec![
+            Wall(0), Wall(0), Wall(0),
+            Wall(0), Floor(1), Wall(0),
+            Wall(0), Wall(0), Wall(0)
+        ]);
+
+        // The floor and the four walls around it, the corners show nothing.
+        assert_eq!(drawn_cells(&world), 5);
+    }
+
+    #[test]
+    fn bakes_door_panels() {
+        let world = generated_map(32);
+        let door_count = world.cells.iter().filter(|c| **c == Door).count();
+        let geometry = build_door_geometry(&world, &registry());
+
+        assert_eq!(geometry.quad_count(), door_count * 2);
+    }
+
+    #[test]
+    fn batches_a_large_map() {
+        let world = generated_map(256);
+        let geometry = build_level_geometry(&world, &registry());
+        let doors = build_door_geometry(&world, &registry());
+        let batched = geometry.draw_calls() + doors.draw_calls();
+
+        // One call for the static mesh, one for the door panels.
+        assert_eq!(batched, 2);
+        assert!(per_cell_draw_calls(&world) > 256 * 256);
+    }
+}
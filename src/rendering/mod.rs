@@ -2,4 +2,13 @@
 #[macro_use]
 mod glwrap;
 mod engine_types;
+pub mod level_mesh;
+mod sprites;
+mod overlay;
+pub mod automap;
+pub mod debug;
 pub mod graphics;
+pub mod hud;
+pub mod impacts;
+pub mod text;
+pub mod view_model;
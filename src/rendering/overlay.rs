@@ -0,0 +1,89 @@
+//! Draws flat geometry over the rendered world with the 2D pipeline: no
+//! view matrix, no depth test.
+//!
+//! Vertices share the layout of `level_mesh`, with `x` and `y` in pixels
+//! from the bottom left corner of the screen, and sample the world texture
+//! array.
+
+use gl::types::GLuint;
+use crate::rendering::{
+    engine_types::Uniforms,
+    glwrap::{ GlShaderProgram, GlShader, GlTextureArray, GlVertexArray, Bind },
+    level_mesh::LevelGeometry
+};
+
+pub struct Overlay {
+    shader_program: GlShaderProgram,
+    uniforms: Uniforms,
+    vertex_array: GlVertexArray,
+    index_count: i32
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        let a_position: GLuint = 0;
+        let a_tex_coord: GLuint = 1;
+        let a_layer: GLuint = 2;
+        let shaders = vec![
+            GlShader::from_file("shaders/2dvertexShader.glsl")
+                .unwrap(),
+            GlShader::from_file("shaders/worldFragmentShader.glsl")
+                .unwrap()
+        ];
+        let shader_program = GlShaderProgram::new(
+            &shaders, &attrib_bindings![a_position, a_tex_coord, a_layer]
+        ).unwrap();
+        let uniforms = Uniforms::from_program(&shader_program);
+
+        Self {
+            shader_program,
+            uniforms,
+            vertex_array: GlVertexArray::from_vertex_buffer(
+                &[], &[], &[(a_position, 3), (a_tex_coord, 2), (a_layer, 1)]
+            ),
+            index_count: 0
+        }
+    }
+
+    pub fn update(&mut self, geometry: &LevelGeometry) {
+        self.vertex_array.update(&geometry.vertices, &geometry.indices);
+        self.index_count = geometry.indices.len() as i32;
+    }
+
+    /// Draws the geometry over whatever the screen holds.
+    /// * `textures`: the texture array the layers of the geometry refer to.
+    /// * `width`, `height`: size of the screen in pixels.
+    /// Returns: the draw calls issued.
+    pub fn draw(&self, textures: &GlTextureArray, width: f32, height: f32) -> usize {
+        if self.index_count == 0 {
+            return 0;
+        }
+
+        let _prg_bind = Bind::new(&self.shader_program);
+        let projection_matrix: [[f32; 4]; 4] = glm::ortho(
+            0.0, width, 0.0, height, -1.0, 1.0
+        ).into();
+        let model_matrix: [[f32; 4]; 4] = glm::identity::<f32, glm::U4>().into();
+
+        unsafe {
+            gl::UniformMatrix4fv(self.uniforms.u_projection_matrix, 1, gl::FALSE,
+                &projection_matrix[0][0] as *const f32);
+            gl::UniformMatrix4fv(self.uniforms.u_model_matrix, 1, gl::FALSE,
+                &model_matrix[0][0] as *const f32);
+            gl::ActiveTexture(gl::TEXTURE0);
+            // Over everything, whatever the depth of the world below.
+            gl::Disable(gl::DEPTH_TEST);
+        }
+
+        let _texture_bind = Bind::new(textures);
+        let _vertex_bind = Bind::new(&self.vertex_array);
+
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT,
+                0 as *const std::ffi::c_void);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        1
+    }
+}
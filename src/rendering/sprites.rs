@@ -0,0 +1,140 @@
+//! Draws `Model::Sprite` and `Model::DirectionalSprite` render elements as
+//! billboards, quads that always face the camera, rebuilt every frame from
+//! wherever the sprites stand.
+//!
+//! Vertices share the layout of `level_mesh`, so that sprites draw with the
+//! world program and texture array.
+
+use std::cmp::Ordering;
+use glm::Vec3;
+use gl::types::GLuint;
+use crate::game::{
+    model::TextureIndex,
+    sprites::rotation_column
+};
+use crate::rendering::{
+    glwrap::{ GlVertexArray, Bind },
+    graphics::{ Model, RenderElement, ViewSettings },
+    level_mesh::{ LevelGeometry, CELL_SIZE }
+};
+
+/// Width and height of a sprite in world units. A sprite image covers as
+/// many texels per unit as a wall does.
+pub const SPRITE_SIZE: f32 = CELL_SIZE;
+
+const SPRITE_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+/// Builds a quad per sprite in front of the camera, standing on the floor
+/// and facing the camera, farthest first so that they blend over each other
+/// in order.
+pub fn build_sprite_geometry(elements: &[RenderElement], view: &ViewSettings) -> LevelGeometry {
+    let mut geometry = LevelGeometry::default();
+    let mut visible: Vec<_> = elements.iter()
+        .filter_map(|element| {
+            let layer = match element.model {
+                Model::Sprite(sprite) => sprite.layer,
+                Model::DirectionalSprite(sprite) => sprite.first_layer
+                    + rotation_column(element.facing, view.facing) as TextureIndex,
+                Model::Tile(_) => return None
+            };
+            let depth = glm::dot(&(element.pos - view.pos), &view.facing);
+
+            Some((depth, element.pos, layer))
+        })
+        .filter(|(depth, _, _)| *depth > 0.0)
+        .collect();
+
+    visible.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    // Billboards lie on the plane of the screen, like in the old games,
+    // rather than turning towards the eye each.
+    let right = Vec3::new(view.facing.y, -view.facing.x, 0.0).normalize() * SPRITE_SIZE / 2.0;
+    let up = Vec3::new(0.0, 0.0, SPRITE_SIZE);
+
+    for (_, pos, layer) in visible {
+        let base = pos * CELL_SIZE;
+
+        geometry.push_quad(
+            [base - right, base + right, base + right + up, base - right + up],
+            SPRITE_UVS, layer
+        );
+    }
+
+    geometry
+}
+
+/// Sprite quads, uploaded anew every frame.
+pub struct SpriteBatch {
+    vertex_array: GlVertexArray,
+    index_count: i32
+}
+
+impl SpriteBatch {
+    /// * `attributes`: position, texture coordinates and layer attributes.
+    pub fn new(attributes: &[(GLuint, usize)]) -> Self {
+        Self {
+            vertex_array: GlVertexArray::from_vertex_buffer(&[], &[], attributes),
+            index_count: 0
+        }
+    }
+
+    pub fn update(&mut self, elements: &[RenderElement], view: &ViewSettings) {
+        let geometry = build_sprite_geometry(elements, view);
+
+        self.vertex_array.update(&geometry.vertices, &geometry.indices);
+        self.index_count = geometry.indices.len() as i32;
+    }
+
+    /// Returns: the draw calls issued.
+    pub fn draw(&self) -> usize {
+        if self.index_count == 0 {
+            return 0;
+        }
+
+        let _vertex_bind = Bind::new(&self.vertex_array);
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, self.index_count, gl::UNSIGNED_INT,
+                0 as *const std::ffi::c_void);
+        }
+
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::graphics::Sprite;
+
+    fn sprite(x: f32, y: f32, layer: u16) -> RenderElement {
+        RenderElement {
+            model: Model::Sprite(Sprite { layer }),
+            pos: Vec3::new(x, y, 0.0),
+            facing: Vec3::new(1.0, 0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn sorts_and_faces_the_camera() {
+        let view = ViewSettings {
+            pos: Vec3::new(1.0, 1.0, 0.0),
+            facing: Vec3::new(0.0, 1.0, 0.0),
+            height: 1.77
+        };
+        let elements = [sprite(1.0, 3.0, 1), sprite(1.0, -2.0, 2), sprite(2.0, 6.0, 3)];
+        let geometry = build_sprite_geometry(&elements, &view);
+
+        // The sprite behind the camera is left out, the farthest comes first.
+        assert_eq!(geometry.quad_count(), 2);
+        assert_eq!(geometry.vertices[5], 3.0);
+        assert_eq!(geometry.vertices[6 * 4 + 5], 1.0);
+
+        let corner = |i: usize| Vec3::new(
+            geometry.vertices[i * 6], geometry.vertices[i * 6 + 1], geometry.vertices[i * 6 + 2]
+        );
+        let normal = (corner(1) - corner(0)).cross(&(corner(2) - corner(0)));
+
+        assert!(glm::dot(&normal, &view.facing) < 0.0);
+        assert_eq!(corner(2).z, SPRITE_SIZE);
+    }
+}
@@ -0,0 +1,356 @@
+//! Draws text over the screen with a bitmap font: an atlas image cut in a
+//! grid of cells, one glyph per cell in the order of the character codes,
+//! and a RON file telling where each glyph sits in its cell.
+//!
+//! Text goes through the 2D pipeline, like `overlay`, with positions in
+//! pixels from the bottom left corner of the screen.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::Deserialize;
+use gl::types::{ GLint, GLuint };
+use glm::Vec3;
+use crate::rendering::{
+    engine_types::Uniforms,
+    glwrap::{ GlShaderProgram, GlShader, GlTexture, GlVertexArray, Bind },
+    level_mesh::LevelGeometry
+};
+
+/// On-disk layout of a font.
+#[derive(Deserialize)]
+struct FontRon {
+    image: String,
+    cell_width: usize,
+    cell_height: usize,
+    columns: usize,
+    rows: usize,
+    spacing: usize,
+    glyphs: BTreeMap<char, GlyphRon>
+}
+
+/// Where a glyph starts in its cell and how wide it is, in pixels.
+#[derive(Copy, Clone, Debug, Deserialize)]
+struct GlyphRon {
+    left: usize,
+    width: usize
+}
+
+#[derive(Clone, Debug)]
+pub struct Font {
+    pub image: String,
+    /// Size of the cells of the atlas, in pixels.
+    pub cell_width: usize,
+    pub cell_height: usize,
+    /// Cells across the atlas.
+    pub columns: usize,
+    /// Cells down the atlas.
+    pub rows: usize,
+    /// Pixels between glyphs.
+    pub spacing: usize,
+    glyphs: BTreeMap<char, GlyphRon>
+}
+
+impl Font {
+    /// Loads a font from a RON file.
+    /// Returns: `Result<Font, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let source = match std::fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.as_ref().display(), error));
+            }
+        };
+
+        Self::parse(&source).map_err(|e| format!("{}: {}", path.as_ref().display(), e))
+    }
+
+    /// Parses and validates a font from RON source.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let font: FontRon = ron::de::from_str(source)
+            .map_err(|e| format!("{}", e))?;
+
+        if font.cell_width == 0 || font.cell_height == 0 {
+            return Err(format!("invalid cell size {}x{}", font.cell_width, font.cell_height));
+        }
+
+        for (character, glyph) in font.glyphs.iter() {
+            if *character as usize >= font.columns * font.rows {
+                return Err(format!(
+                    "glyph {:?} falls outside the {}x{} atlas", character, font.columns, font.rows
+                ));
+            }
+
+            if glyph.left + glyph.width > font.cell_width {
+                return Err(format!("glyph {:?} overflows its cell", character));
+            }
+        }
+
+        Ok(Self {
+            image: font.image,
+            cell_width: font.cell_width,
+            cell_height: font.cell_height,
+            columns: font.columns,
+            rows: font.rows,
+            spacing: font.spacing,
+            glyphs: font.glyphs
+        })
+    }
+
+    pub fn line_height(&self, scale: f32) -> f32 {
+        self.cell_height as f32 * scale
+    }
+
+    /// Size of text as `layout` places it, in pixels.
+    pub fn measure(&self, scale: f32, text: &str) -> (f32, f32) {
+        let width = text.split('\n')
+            .map(|line| {
+                let advance: usize = line.chars()
+                    .filter_map(|c| self.glyph(c))
+                    .map(|glyph| glyph.width + self.spacing)
+                    .sum();
+
+                advance.saturating_sub(self.spacing) as f32 * scale
+            })
+            .fold(0.0, f32::max);
+
+        (width, text.split('\n').count() as f32 * self.line_height(scale))
+    }
+
+    /// Builds a quad per glyph of some text. Characters the font lacks show
+    /// as `?`, when it has that.
+    /// * `pos`: bottom left corner of the first line, in pixels.
+    /// * `scale`: screen pixels per font pixel.
+    pub fn layout(&self, pos: (f32, f32), scale: f32, text: &str) -> LevelGeometry {
+        let mut geometry = LevelGeometry::default();
+        let atlas_width = (self.columns * self.cell_width) as f32;
+        let atlas_height = (self.rows * self.cell_height) as f32;
+        let height = self.line_height(scale);
+        let (mut x, mut y) = pos;
+
+        for c in text.chars() {
+            if c == '\n' {
+                x = pos.0;
+                y -= height;
+                continue;
+            }
+
+            let (cell, glyph) = match self.glyphs.get_key_value(&c)
+                .or_else(|| self.glyphs.get_key_value(&'?')) {
+                Some((cell, glyph)) => (*cell as usize, *glyph),
+                None => continue
+            };
+            let width = glyph.width as f32 * scale;
+
+            if !c.is_whitespace() {
+                let left = ((cell % self.columns) * self.cell_width + glyph.left) as f32 / atlas_width;
+                let right = left + glyph.width as f32 / atlas_width;
+                let top = ((cell / self.columns) * self.cell_height) as f32 / atlas_height;
+                let bottom = top + self.cell_height as f32 / atlas_height;
+
+                geometry.push_quad(
+                    [
+                        Vec3::new(x, y, 0.0),
+                        Vec3::new(x + width, y, 0.0),
+                        Vec3::new(x + width, y + height, 0.0),
+                        Vec3::new(x, y + height, 0.0)
+                    ],
+                    [[left, bottom], [right, bottom], [right, top], [left, top]],
+                    0
+                );
+            }
+
+            x += width + self.spacing as f32 * scale;
+        }
+
+        geometry
+    }
+
+    fn glyph(&self, c: char) -> Option<&GlyphRon> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+}
+
+/// Draws text with a font, one call at a time.
+pub struct TextRenderer {
+    font: Font,
+    texture: GlTexture,
+    shader_program: GlShaderProgram,
+    uniforms: Uniforms,
+    u_color: GLint,
+    /// Glyph quads of the latest call.
+    vertex_array: GlVertexArray,
+    /// Size of the screen, in pixels.
+    screen: (f32, f32)
+}
+
+impl TextRenderer {
+    /// Loads a font and its atlas.
+    /// * `path`: RON font file, usually `textures/font.ron`.
+    /// Returns: `Result<TextRenderer, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let font = Font::from_file(&path)?;
+        let texture = GlTexture::from_file(&font.image)?;
+
+        if texture.width() != font.columns * font.cell_width
+            || texture.height() != font.rows * font.cell_height {
+            return Err(format!(
+                "{}: expected a {}x{} atlas, found {}x{}",
+                font.image, font.columns * font.cell_width, font.rows * font.cell_height,
+                texture.width(), texture.height()
+            ));
+        }
+
+        let a_position: GLuint = 0;
+        let a_tex_coord: GLuint = 1;
+        let a_layer: GLuint = 2;
+        let shaders = vec![
+            GlShader::from_file("shaders/2dvertexShader.glsl")?,
+            GlShader::from_file("shaders/fontFragmentShader.glsl")?
+        ];
+        let shader_program = GlShaderProgram::new(
+            &shaders, &attrib_bindings![a_position, a_tex_coord, a_layer]
+        )?;
+        let uniforms = Uniforms::from_program(&shader_program);
+        let u_color = shader_program.uniform_location("u_color\0");
+
+        Ok(Self {
+            font,
+            texture,
+            shader_program,
+            uniforms,
+            u_color,
+            vertex_array: GlVertexArray::from_vertex_buffer(
+                &[], &[], &[(a_position, 3), (a_tex_coord, 2), (a_layer, 1)]
+            ),
+            screen: (800.0, 600.0)
+        })
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    /// * `width`, `height`: size of the screen in pixels.
+    pub fn set_screen_size(&mut self, width: f32, height: f32) {
+        self.screen = (width, height);
+    }
+
+    /// Draws text over whatever the screen holds.
+    /// * `pos`: bottom left corner of the first line, in pixels from the
+    ///   bottom left corner of the screen.
+    /// * `scale`: screen pixels per font pixel.
+    /// * `color`: red, green, blue and alpha the white glyphs turn into.
+    /// Returns: the draw calls issued.
+    pub fn draw_text(&self, pos: (f32, f32), scale: f32, color: [f32; 4], text: &str) -> usize {
+        self.draw_quads(&self.texture, &self.font.layout(pos, scale, text), color)
+    }
+
+    /// Draws quads of another image the way glyphs are drawn, over
+    /// whatever the screen holds.
+    /// * `geometry`: quads in pixels, like those of `Font::layout`.
+    /// * `color`: red, green, blue and alpha to tint the image with.
+    /// Returns: the draw calls issued.
+    pub fn draw_quads(&self, texture: &GlTexture, geometry: &LevelGeometry, color: [f32; 4]) -> usize {
+        if geometry.indices.is_empty() {
+            return 0;
+        }
+
+        self.vertex_array.update(&geometry.vertices, &geometry.indices);
+
+        let _prg_bind = Bind::new(&self.shader_program);
+        let projection_matrix: [[f32; 4]; 4] = glm::ortho(
+            0.0, self.screen.0, 0.0, self.screen.1, -1.0, 1.0
+        ).into();
+        let model_matrix: [[f32; 4]; 4] = glm::identity::<f32, glm::U4>().into();
+
+        unsafe {
+            gl::UniformMatrix4fv(self.uniforms.u_projection_matrix, 1, gl::FALSE,
+                &projection_matrix[0][0] as *const f32);
+            gl::UniformMatrix4fv(self.uniforms.u_model_matrix, 1, gl::FALSE,
+                &model_matrix[0][0] as *const f32);
+            gl::Uniform4f(self.u_color, color[0], color[1], color[2], color[3]);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::Disable(gl::DEPTH_TEST);
+            // Glyph edges are smoothed in the alpha of the atlas, images cut out
+            // in it.
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        let _texture_bind = Bind::new(texture);
+        let _vertex_bind = Bind::new(&self.vertex_array);
+
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, geometry.indices.len() as i32, gl::UNSIGNED_INT,
+                0 as *const std::ffi::c_void);
+            gl::Disable(gl::BLEND);
+            gl::Enable(gl::DEPTH_TEST);
+        }
+
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::level_mesh::FLOATS_PER_VERTEX;
+
+    const FONT: &str = r#"FontRon(
+        image: "font.png",
+        cell_width: 16,
+        cell_height: 16,
+        columns: 16,
+        rows: 16,
+        spacing: 1,
+        glyphs: {
+            ' ': (left: 0, width: 4),
+            '?': (left: 1, width: 6),
+            'A': (left: 1, width: 7),
+            'i': (left: 2, width: 2),
+        },
+    )"#;
+
+    fn corner(geometry: &LevelGeometry, quad: usize, corner: usize) -> [f32; 5] {
+        let start = (quad * 4 + corner) * FLOATS_PER_VERTEX;
+        let v = &geometry.vertices[start..start + 5];
+
+        [v[0], v[1], v[2], v[3], v[4]]
+    }
+
+    #[test]
+    fn lays_glyphs_side_by_side() {
+        let font = Font::parse(FONT).unwrap();
+        let geometry = font.layout((10.0, 20.0), 2.0, "Ai A");
+
+        // Spaces advance, but draw nothing.
+        assert_eq!(geometry.quad_count(), 3);
+        // 'A' is cell 65: column 1, row 4 of the atlas.
+        assert_eq!(corner(&geometry, 0, 0), [10.0, 20.0, 0.0, 17.0 / 256.0, 80.0 / 256.0]);
+        assert_eq!(corner(&geometry, 0, 2), [24.0, 52.0, 0.0, 24.0 / 256.0, 64.0 / 256.0]);
+        // Then 'i', one font pixel apart.
+        assert_eq!(corner(&geometry, 1, 0)[0], 26.0);
+        assert_eq!(corner(&geometry, 2, 0)[0], 26.0 + 6.0 + 8.0 + 2.0);
+        assert_eq!(font.measure(2.0, "Ai A"), (46.0, 32.0));
+    }
+
+    #[test]
+    fn breaks_lines_and_stands_in_for_unknown_characters() {
+        let font = Font::parse(FONT).unwrap();
+        let geometry = font.layout((0.0, 100.0), 1.0, "A\nZ");
+
+        assert_eq!(geometry.quad_count(), 2);
+        assert_eq!(&corner(&geometry, 1, 0)[0..2], &[0.0, 84.0]);
+        // 'Z' is missing, '?' is cell 63.
+        assert_eq!(corner(&geometry, 1, 0)[3], (15.0 * 16.0 + 1.0) / 256.0);
+        assert_eq!(font.measure(1.0, "A\nZ"), (7.0, 32.0));
+    }
+
+    #[test]
+    fn rejects_glyphs_outside_the_atlas() {
+        let small = FONT.replace("rows: 16", "rows: 4");
+
+        assert!(Font::parse(&small).is_err());
+        assert!(Font::parse(&FONT.replace("width: 7", "width: 16")).is_err());
+    }
+}
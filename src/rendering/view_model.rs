@@ -0,0 +1,127 @@
+//! The weapon of the player, drawn at the bottom of the screen over the
+//! world, swaying as the player walks.
+
+use std::f32::consts::PI;
+use glm::Vec3;
+use crate::game::model::TextureIndex;
+use crate::rendering::level_mesh::LevelGeometry;
+
+/// Height of the weapon on screen, as a share of the screen height.
+pub const VIEW_MODEL_SIZE: f32 = 0.5;
+/// Sideways sway of the weapon at full walking speed, as a share of its
+/// size.
+pub const BOB_SWAY: f32 = 0.08;
+/// How far the weapon dips at full walking speed, as a share of its size.
+pub const BOB_DIP: f32 = 0.05;
+/// Sways per second at full walking speed, one for each step.
+pub const BOB_RATE: f32 = 1.0;
+/// How quickly the sway follows the walking speed, per second.
+const BOB_EASE: f32 = 8.0;
+
+const VIEW_MODEL_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+/// What to show of the weapon this frame.
+#[derive(Copy, Clone, Debug)]
+pub struct WeaponView {
+    /// Texture array layer of the frame.
+    pub layer: TextureIndex,
+    /// How far the weapon sways from rest, as shares of its size: right and
+    /// down.
+    pub bob: (f32, f32)
+}
+
+/// Sways the weapon as the player walks.
+#[derive(Default)]
+pub struct ViewModelBob {
+    phase: f32,
+    /// Follows the walking speed, from 0 to 1.
+    amount: f32
+}
+
+impl ViewModelBob {
+    /// * `speed`: walking speed, from 0 (still) to 1 (full speed).
+    /// * `dt`: seconds since the previous frame.
+    pub fn update(&mut self, speed: f32, dt: f32) {
+        let speed = speed.clamp(0.0, 1.0);
+
+        self.amount += (speed - self.amount) * (dt * BOB_EASE).min(1.0);
+        self.phase = (self.phase + dt * BOB_RATE * PI * self.amount) % (2.0 * PI);
+    }
+
+    /// Returns: the sway of the weapon, see `WeaponView::bob`.
+    pub fn offset(&self) -> (f32, f32) {
+        // Swings on an arc, lowest in the middle.
+        (
+            self.phase.cos() * BOB_SWAY * self.amount,
+            self.phase.sin().abs() * BOB_DIP * self.amount
+        )
+    }
+}
+
+/// Builds the quad of the weapon, centered at the bottom of the screen.
+/// * `width`, `height`: size of the screen in pixels.
+pub fn build_view_model_geometry(weapon: &WeaponView, width: f32, height: f32) -> LevelGeometry {
+    let mut geometry = LevelGeometry::default();
+    let size = height * VIEW_MODEL_SIZE;
+    let left = (width - size) / 2.0 + weapon.bob.0 * size;
+    let bottom = -weapon.bob.1 * size;
+
+    geometry.push_quad(
+        [
+            Vec3::new(left, bottom, 0.0),
+            Vec3::new(left + size, bottom, 0.0),
+            Vec3::new(left + size, bottom + size, 0.0),
+            Vec3::new(left, bottom + size, 0.0)
+        ],
+        VIEW_MODEL_UVS, weapon.layer
+    );
+
+    geometry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sways_while_walking() {
+        let mut bob = ViewModelBob::default();
+
+        bob.update(0.0, 1.0);
+        assert_eq!(bob.offset(), (0.0, 0.0));
+
+        let mut lowest = 0.0f32;
+        let mut widest = 0.0f32;
+
+        for _ in 0..120 {
+            bob.update(1.0, 1.0 / 60.0);
+            widest = widest.max(bob.offset().0.abs());
+            lowest = lowest.max(bob.offset().1);
+        }
+
+        assert!(widest > BOB_SWAY * 0.9 && widest <= BOB_SWAY);
+        assert!(lowest > BOB_DIP * 0.9 && lowest <= BOB_DIP);
+
+        for _ in 0..60 {
+            bob.update(0.0, 1.0 / 60.0);
+        }
+
+        assert!(bob.offset().0.abs() < 1e-3 && bob.offset().1 < 1e-3);
+    }
+
+    #[test]
+    fn stands_at_the_bottom_of_the_screen() {
+        let still = WeaponView { layer: 7, bob: (0.0, 0.0) };
+        let geometry = build_view_model_geometry(&still, 800.0, 600.0);
+
+        assert_eq!(geometry.quad_count(), 1);
+        // First corner: bottom left, then its layer.
+        assert_eq!(&geometry.vertices[0..3], &[250.0, 0.0, 0.0]);
+        assert_eq!(geometry.vertices[5], 7.0);
+
+        let swayed = WeaponView { layer: 7, bob: (0.1, 0.05) };
+        let geometry = build_view_model_geometry(&swayed, 800.0, 600.0);
+
+        assert_eq!(&geometry.vertices[0..2], &[280.0, -15.0]);
+    }
+}
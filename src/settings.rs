@@ -0,0 +1,150 @@
+//! Window and display settings, read from `settings.ron`.
+
+use std::path::Path;
+use serde::{ Serialize, Deserialize };
+
+/// Narrowest and widest vertical field of view allowed, in degrees.
+const FIELD_OF_VIEW_RANGE: (f32, f32) = (30.0, 120.0);
+
+/// How the window covers the screen.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    /// Takes over the display, switching it to the resolution of the
+    /// settings.
+    Fullscreen,
+    /// A window without borders covering the whole desktop, at the
+    /// resolution of the desktop.
+    Borderless
+}
+
+/// On-disk layout of `settings.ron`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SettingsRon {
+    width: u32,
+    height: u32,
+    #[serde(default = "default_window_mode")]
+    window_mode: WindowMode,
+    /// Waits for the display to show a frame before drawing the next one.
+    #[serde(default = "default_vsync")]
+    vsync: bool,
+    /// Vertical field of view, in degrees.
+    #[serde(default = "default_field_of_view")]
+    field_of_view: f32
+}
+
+impl Default for SettingsRon {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            window_mode: default_window_mode(),
+            vsync: default_vsync(),
+            field_of_view: default_field_of_view()
+        }
+    }
+}
+
+fn default_window_mode() -> WindowMode {
+    WindowMode::Windowed
+}
+
+fn default_vsync() -> bool {
+    true
+}
+
+fn default_field_of_view() -> f32 {
+    72.0
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// Size of the window, in pixels.
+    pub width: u32,
+    pub height: u32,
+    pub window_mode: WindowMode,
+    pub vsync: bool,
+    /// Vertical field of view, in radians.
+    pub field_of_view: f32
+}
+
+impl Settings {
+    /// Loads the settings from a RON file, writing the default settings
+    /// there first if the file does not exist.
+    /// * `path`: usually `settings.ron`.
+    /// Returns: `Result<Settings, String>`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+
+        if !path.exists() {
+            let defaults = ron::ser::to_string_pretty(
+                &SettingsRon::default(), ron::ser::PrettyConfig::default()
+            ).map_err(|e| format!("{}", e))?;
+
+            std::fs::write(path, defaults)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        let source = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(error) => {
+                return Err(format!("{}: {}", path.display(), error));
+            }
+        };
+
+        Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parses and validates settings from RON source.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let settings: SettingsRon = ron::de::from_str(source)
+            .map_err(|e| format!("{}", e))?;
+        let (narrowest, widest) = FIELD_OF_VIEW_RANGE;
+
+        if settings.width == 0 || settings.height == 0 {
+            return Err(format!("invalid resolution {}x{}", settings.width, settings.height));
+        }
+
+        if !(narrowest..=widest).contains(&settings.field_of_view) {
+            return Err(format!(
+                "field of view {} out of range, expected {} to {} degrees",
+                settings.field_of_view, narrowest, widest
+            ));
+        }
+
+        Ok(Self {
+            width: settings.width,
+            height: settings.height,
+            window_mode: settings.window_mode,
+            vsync: settings.vsync,
+            field_of_view: settings.field_of_view.to_radians()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_in_what_is_left_out() {
+        let defaults = ron::ser::to_string(&SettingsRon::default()).unwrap();
+        let settings = Settings::parse(&defaults).unwrap();
+
+        assert_eq!((settings.width, settings.height), (800, 600));
+        assert!((settings.field_of_view - 72.0f32.to_radians()).abs() < 1e-6);
+
+        let settings = Settings::parse("(width: 1920, height: 1080, window_mode: Borderless)")
+            .unwrap();
+
+        assert_eq!(settings.window_mode, WindowMode::Borderless);
+        assert!(settings.vsync);
+    }
+
+    #[test]
+    fn rejects_odd_values() {
+        assert!(Settings::parse("(width: 0, height: 600)").is_err());
+        assert!(Settings::parse("(width: 800, height: 600, field_of_view: 179.0)")
+            .unwrap_err().contains("field of view"));
+    }
+}
@@ -1,31 +1,61 @@
-use crate::game::model::{Game, Player, DungeonFloor};
-use crate::game::model::DungeonCell::{Empty, Floor, Wall};
-use slotmap::SlotMap;
+use crate::game::model::{Game, DungeonFloor, DungeonCell};
+use crate::game::model::DungeonCell::{Empty, Floor, Wall, Door};
 use glm::Vec3;
 
+const W: DungeonCell = Wall(0);
+const F: DungeonCell = Floor(1);
+const E: DungeonCell = Empty;
+
 pub fn build_experimental_game() -> Game {
-    Game {
-        player: Player{
-            pos: Vec3::new(1.0, 8.0, 0.0),
-            angle: 0.0,
-            direction: Vec3::new(1.0, 0.0, 0.0),
-            speed: Vec3::new(0.0, 0.0, 0.0),
-        },
-        current_map: DungeonFloor {
-            width: 10, height: 10,
-            cells: vec![
-                Empty, Empty, Empty, Wall,  Wall,  Wall,  Wall,  Empty, Empty, Empty,
-                Empty, Empty, Empty, Wall,  Floor, Floor, Wall,  Empty, Empty, Empty,
-                Wall,  Wall,  Wall,  Wall,  Floor, Floor, Wall,  Empty, Empty, Empty,
-                Wall,  Floor, Floor, Floor, Floor, Floor, Wall,  Wall,  Wall,  Wall,
-                Wall,  Floor, Floor, Floor, Floor, Floor, Floor, Floor, Floor, Wall,
-                Wall,  Floor, Floor, Wall,  Floor, Floor, Floor, Floor, Floor, Wall,
-                Wall,  Floor, Floor, Floor, Floor, Floor, Wall,  Wall,  Wall,  Wall,
-                Wall,  Floor, Floor, Floor, Floor, Floor, Wall,  Empty, Empty, Empty,
-                Wall,  Floor, Floor, Floor, Floor, Floor, Wall,  Empty, Empty, Empty,
-                Wall,  Wall,  Wall,  Wall,  Wall,  Wall,  Wall,  Empty, Empty, Empty
+    Game::new(
+        DungeonFloor::new(
+            10, 10,
+            vec![
+                E, E, E, W, W, W, W, E, E, E,
+                E, E, E, W, F, F, W, E, E, E,
+                W, W, W, W, F, F, W, E, E, E,
+                W, F, F, F, F, F, W, W, W, W,
+                W, F, F, F, F, F, F, F, F, W,
+                W, F, F, W, F, F, F, F, F, W,
+                W, F, F, F, F, F, W, W, W, W,
+                W, F, F, F, F, F, W, E, E, E,
+                W, F, F, F, F, F, W, E, E, E,
+                W, W, W, W, W, W, W, E, E, E
             ]
-        },
-        entities: SlotMap::with_key()
+        ),
+        Vec3::new(1.0, 8.0, 0.0),
+        0.0
+    )
+}
+
+/// A walled map with a pillar every few cells and a door here and there.
+pub fn generated_map(size: usize) -> DungeonFloor {
+    let mut cells = Vec::with_capacity(size * size);
+
+    for y in 0..size {
+        for x in 0..size {
+            let border = x == 0 || y == 0 || x == size - 1 || y == size - 1;
+            cells.push(if border || (x % 4 == 0 && y % 4 == 0) {
+                W
+            } else if x % 16 == 2 && y % 16 == 2 {
+                Door
+            } else {
+                F
+            });
+        }
     }
+
+    DungeonFloor::new(size, size, cells)
+}
+
+/// Draw calls the per-cell renderer issued: a wall took one call, a walkable
+/// cell one for the floor and one for the ceiling, plus one for the panel of
+/// a door.
+pub fn per_cell_draw_calls(world: &DungeonFloor) -> usize {
+    world.cells.iter().map(|cell| match cell {
+        Empty => 0,
+        Wall(_) => 1,
+        Floor(_) => 2,
+        Door | DungeonCell::OpenDoor => 3
+    }).sum()
 }